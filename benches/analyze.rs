@@ -0,0 +1,50 @@
+use astra_logger_rs::analyzer::Analyzer;
+use astra_logger_rs::classifier::Classifier;
+use astra_logger_rs::formatter::Logs;
+use astra_logger_rs::parser::{LogFormat, LogParser};
+use astra_logger_rs::rules::RuleSet;
+use astra_logger_rs::scanner::LogStats;
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use std::path::{Path, PathBuf};
+
+// Пропускная способность (строк в секунду) на log/daemon.log
+fn analyze_daemon_log(c: &mut Criterion) {
+    let content = std::fs::read_to_string("log/daemon.log").unwrap();
+    let lines: Vec<&str> = content.lines().collect();
+    let path = PathBuf::from("log/daemon.log");
+    let parser = LogFormat::Syslog.parser();
+
+    let mut group = c.benchmark_group("daemon.log");
+    group.throughput(Throughput::Elements(lines.len() as u64));
+
+    let classifier = Classifier::default();
+    group.bench_function("builtin", |b| {
+        b.iter(|| analyze(&lines, parser.as_ref(), &classifier, &path))
+    });
+
+    let rules = RuleSet::load(Path::new("rules.example.toml")).unwrap();
+    let classifier = Classifier::new(rules);
+    group.bench_function("rules", |b| {
+        b.iter(|| analyze(&lines, parser.as_ref(), &classifier, &path))
+    });
+
+    group.finish();
+}
+
+fn analyze(lines: &[&str], parser: &dyn LogParser, classifier: &Classifier, path: &Path) -> usize {
+    let mut logs = Logs::new();
+    let mut stats = LogStats::new();
+    let mut analyzer = Analyzer::new(classifier);
+    analyzer.add_consumer(&mut logs);
+    analyzer.add_consumer(&mut stats);
+    for line in lines {
+        analyzer
+            .analyze_line(parser, line, path.to_path_buf())
+            .unwrap();
+    }
+    drop(analyzer);
+    stats.total_messages
+}
+
+criterion_group!(benches, analyze_daemon_log);
+criterion_main!(benches);
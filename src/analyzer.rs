@@ -0,0 +1,157 @@
+use crate::classifier::Classifier;
+use crate::formatter::{LevelFilter, LogEntry, Logs};
+use crate::parser::LogParser;
+use crate::query::Query;
+use crate::scanner::LogStats;
+use std::io;
+use std::path::PathBuf;
+use tokio::sync::mpsc::UnboundedSender;
+
+// Получатель разобранных записей: хранилище, счётчики, экспорт
+pub trait Consumer {
+    fn consume(&mut self, entry: &LogEntry) -> io::Result<()>;
+
+    // Сброс буферов, чтобы данные были видны сразу (режим --follow)
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+
+    // Вызывается один раз после последней записи
+    fn finish(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Consumer for Logs {
+    fn consume(&mut self, entry: &LogEntry) -> io::Result<()> {
+        self.push(entry.clone());
+        Ok(())
+    }
+}
+
+impl Consumer for LogStats {
+    fn consume(&mut self, entry: &LogEntry) -> io::Result<()> {
+        self.record(entry.level);
+        Ok(())
+    }
+}
+
+// Передача записей в живой TUI; ошибка означает, что TUI уже закрыт
+impl Consumer for UnboundedSender<LogEntry> {
+    fn consume(&mut self, entry: &LogEntry) -> io::Result<()> {
+        self.send(entry.clone())
+            .map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "TUI closed"))
+    }
+}
+
+// Разбирает и классифицирует каждую строку один раз, после чего отдаёт
+// одну и ту же запись всем получателям, поэтому их данные всегда согласованы
+pub struct Analyzer<'a> {
+    classifier: &'a Classifier,
+    consumers: Vec<&'a mut dyn Consumer>,
+    // Записи других уровней не доходят до получателей (--log-level)
+    level_filter: Option<LevelFilter>,
+    // Запрос по полям (--query)
+    query: Option<&'a Query>,
+}
+
+impl<'a> Analyzer<'a> {
+    pub fn new(classifier: &'a Classifier) -> Self {
+        Analyzer {
+            classifier,
+            consumers: Vec::new(),
+            level_filter: None,
+            query: None,
+        }
+    }
+
+    pub fn set_level_filter(&mut self, filter: Option<LevelFilter>) {
+        self.level_filter = filter;
+    }
+
+    pub fn level_filter(&self) -> Option<LevelFilter> {
+        self.level_filter
+    }
+
+    pub fn set_query(&mut self, query: Option<&'a Query>) {
+        self.query = query;
+    }
+
+    // Проходит ли запись фильтр по классифицированному уровню и запрос
+    pub fn accepts(&self, entry: &LogEntry) -> bool {
+        self.level_filter
+            .is_none_or(|filter| filter.matches(entry.level))
+            && self.query.is_none_or(|query| query.matches(entry))
+    }
+
+    pub fn add_consumer(&mut self, consumer: &'a mut dyn Consumer) {
+        self.consumers.push(consumer);
+    }
+
+    // Для события из нескольких строк (--multiline) заголовок, время и уровень
+    // берутся из первой строки, остальные дописываются к message и body
+    pub fn entry(&self, parser: &dyn LogParser, line: &str, file_path: PathBuf) -> LogEntry {
+        if let Some(entry) = parser.stored_entry(line, &file_path) {
+            return entry;
+        }
+        let (first, rest) = match line.split_once('\n') {
+            Some((first, rest)) => (first, Some(rest)),
+            None => (line, None),
+        };
+        let parsed = parser.parse(first);
+        let text = parsed.message.as_deref().unwrap_or(first);
+        let (level, rule) = self.classifier.classify(text, &parsed);
+
+        let mut entry = LogEntry::new(level, text.to_string(), parsed.timestamp, file_path);
+        entry.host = parsed.host.as_deref().map(String::from);
+        entry.program = parsed.program.as_deref().map(String::from);
+        entry.pid = parsed.pid;
+        if parsed.body != text {
+            entry.body = Some(parsed.body.to_string());
+        }
+        if let Some(rest) = rest {
+            for text in std::iter::once(&mut entry.message).chain(entry.body.as_mut()) {
+                text.push('\n');
+                text.push_str(rest);
+            }
+        }
+        if let Some(rule) = rule {
+            entry.tags = rule.tags.clone();
+        }
+        entry
+    }
+
+    pub fn analyze_line(
+        &mut self,
+        parser: &dyn LogParser,
+        line: &str,
+        file_path: PathBuf,
+    ) -> io::Result<()> {
+        let entry = self.entry(parser, line, file_path);
+        self.dispatch(&entry)
+    }
+
+    pub fn dispatch(&mut self, entry: &LogEntry) -> io::Result<()> {
+        if !self.accepts(entry) {
+            return Ok(());
+        }
+        for consumer in self.consumers.iter_mut() {
+            consumer.consume(entry)?;
+        }
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        for consumer in self.consumers.iter_mut() {
+            consumer.flush()?;
+        }
+        Ok(())
+    }
+
+    pub fn finish(&mut self) -> io::Result<()> {
+        for consumer in self.consumers.iter_mut() {
+            consumer.finish()?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,251 @@
+use crate::formatter::LogEntry;
+use crate::parser::{detect_format, LogFormat, LogParser, ParsedLine};
+use chrono::{DateTime, Local};
+use serde::Deserialize;
+use std::borrow::Cow;
+use std::io::{self, BufRead, Read};
+use std::path::Path;
+
+// Записи, сохранённые alog раньше (`-j`, Logs::format_to_json): NDJSON или
+// массив JSON, который ArchiveReader перекодирует в NDJSON. Записи отдаются
+// как есть, без повторной классификации.
+//
+// Старые версии формата: сначала были только `level` и `message`, потом
+// появились `date` и `file_path`, но `date` тогда означал время чтения.
+// Записи без поля `ingested_at` считаются старыми: их `date` переносится в
+// `ingested_at`, а время события и поля заголовка берутся из `message`.
+#[derive(Default)]
+pub struct ArchiveParser {
+    // Разбор `message` старых записей, формат определяется по первым записям
+    message: Option<Box<dyn LogParser>>,
+}
+
+impl ArchiveParser {
+    pub fn new() -> Self {
+        ArchiveParser::default()
+    }
+
+    fn upgrade(&self, stored: Stored) -> LogEntry {
+        let mut entry = stored.entry;
+        if let Some(ingested_at) = stored.ingested_at {
+            entry.ingested_at = ingested_at;
+            return entry;
+        }
+        entry.ingested_at = entry.date.take().unwrap_or_default();
+        let Some(parser) = &self.message else {
+            return entry;
+        };
+        let parsed = parser.parse(&entry.message);
+        entry.date = parsed.timestamp.map(|timestamp| timestamp.to_rfc3339());
+        entry.host = parsed.host.map(String::from);
+        entry.program = parsed.program.map(String::from);
+        entry.pid = parsed.pid;
+        if parsed.body != entry.message {
+            entry.body = Some(parsed.body.into_owned());
+        }
+        entry
+    }
+}
+
+// Запись архива. `ingested_at` читается отдельно: старую запись выдаёт только
+// отсутствие поля, пустое значение пишут и новые версии
+#[derive(Deserialize)]
+struct Stored {
+    #[serde(flatten)]
+    entry: LogEntry,
+    ingested_at: Option<String>,
+}
+
+fn read_entry(line: &str) -> Option<Stored> {
+    if !line.trim_start().starts_with('{') {
+        return None;
+    }
+    serde_json::from_str(line).ok()
+}
+
+impl LogParser for ArchiveParser {
+    fn format(&self) -> LogFormat {
+        LogFormat::Archive
+    }
+
+    // Год для syslog-строк старых записей берётся из их времени чтения
+    fn prime(&mut self, head: &[String], reference: DateTime<Local>) {
+        let entries: Vec<Stored> = head.iter().filter_map(|line| read_entry(line)).collect();
+        let messages: Vec<&str> = entries
+            .iter()
+            .map(|stored| stored.entry.message.as_str())
+            .collect();
+        let reference = entries
+            .iter()
+            .filter(|stored| stored.ingested_at.is_none())
+            .find_map(|stored| DateTime::parse_from_rfc3339(stored.entry.date.as_deref()?).ok())
+            .map_or(reference, |date| date.with_timezone(&Local));
+
+        let mut parser = detect_format(&messages).parser();
+        parser.prime(
+            &messages
+                .iter()
+                .map(|message| message.to_string())
+                .collect::<Vec<_>>(),
+            reference,
+        );
+        self.message = Some(parser);
+    }
+
+    fn matches(&self, line: &str) -> bool {
+        read_entry(line).is_some()
+    }
+
+    fn parse<'a>(&self, line: &'a str) -> ParsedLine<'a> {
+        match self.stored_entry(line, Path::new("")) {
+            Some(entry) => ParsedLine {
+                body: Cow::Owned(entry.body.unwrap_or_else(|| entry.message.clone())),
+                level: Some(entry.level),
+                timestamp: entry
+                    .date
+                    .as_deref()
+                    .and_then(|date| DateTime::parse_from_rfc3339(date).ok())
+                    .map(|date| date.with_timezone(&Local)),
+                host: entry.host.map(Cow::Owned),
+                program: entry.program.map(Cow::Owned),
+                pid: entry.pid,
+                message: Some(entry.message),
+            },
+            None => ParsedLine::raw(line),
+        }
+    }
+
+    fn stored_entry(&self, line: &str, file_path: &Path) -> Option<LogEntry> {
+        let mut entry = self.upgrade(read_entry(line)?);
+        // У самых старых записей нет file_path
+        if entry.file_path.as_os_str().is_empty() {
+            entry.file_path = file_path.to_path_buf();
+        }
+        Some(entry)
+    }
+}
+
+// Массив записей: `[` и за ним объект с полем `level` (пустой массив тоже подходит)
+pub fn is_array(head: &[u8]) -> bool {
+    let rest = head.trim_ascii_start();
+    let Some(rest) = rest.strip_prefix(b"[") else {
+        return false;
+    };
+    let rest = rest.trim_ascii_start();
+    rest.starts_with(b"]")
+        || rest.starts_with(b"{") && rest.windows(7).any(|window| window == b"\"level\"")
+}
+
+// Перекодирует массив JSON в NDJSON: по элементу на строку. Пробелы вне строк
+// отбрасываются, поэтому элементы массива с отступами становятся компактными;
+// файл не разбирается целиком и не держится в памяти.
+pub struct ArchiveReader<R> {
+    inner: R,
+    // Текущий элемент с `\n` в конце
+    buf: Vec<u8>,
+    pos: usize,
+    opened: bool,
+    depth: usize,
+    in_string: bool,
+    escape: bool,
+}
+
+impl<R: BufRead> ArchiveReader<R> {
+    pub fn new(inner: R) -> Self {
+        ArchiveReader {
+            inner,
+            buf: Vec::new(),
+            pos: 0,
+            opened: false,
+            depth: 0,
+            in_string: false,
+            escape: false,
+        }
+    }
+
+    // После вызова буфер пуст только в конце потока
+    fn next_element(&mut self) -> io::Result<()> {
+        self.buf.clear();
+        self.pos = 0;
+
+        loop {
+            let chunk = self.inner.fill_buf()?;
+            if chunk.is_empty() {
+                break;
+            }
+            let mut used = 0;
+            let mut done = false;
+            for &byte in chunk {
+                used += 1;
+                if self.in_string {
+                    self.buf.push(byte);
+                    if self.escape {
+                        self.escape = false;
+                    } else if byte == b'\\' {
+                        self.escape = true;
+                    } else if byte == b'"' {
+                        self.in_string = false;
+                    }
+                    continue;
+                }
+                match byte {
+                    b'[' if !self.opened => self.opened = true,
+                    b',' | b']' if self.depth == 0 => {
+                        // После `]` может начаться следующий массив (`cat a.json b.json`)
+                        self.opened &= byte == b',';
+                        if !self.buf.is_empty() {
+                            done = true;
+                            break;
+                        }
+                    }
+                    b'"' => {
+                        self.in_string = true;
+                        self.buf.push(byte);
+                    }
+                    b'[' | b'{' => {
+                        self.depth += 1;
+                        self.buf.push(byte);
+                    }
+                    b']' | b'}' => {
+                        self.depth = self.depth.saturating_sub(1);
+                        self.buf.push(byte);
+                    }
+                    byte if byte.is_ascii_whitespace() => {}
+                    byte => self.buf.push(byte),
+                }
+            }
+            self.inner.consume(used);
+            if done {
+                break;
+            }
+        }
+
+        if !self.buf.is_empty() {
+            self.buf.push(b'\n');
+        }
+        Ok(())
+    }
+}
+
+impl<R: BufRead> Read for ArchiveReader<R> {
+    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        let available = self.fill_buf()?;
+        let len = available.len().min(out.len());
+        out[..len].copy_from_slice(&available[..len]);
+        self.consume(len);
+        Ok(len)
+    }
+}
+
+impl<R: BufRead> BufRead for ArchiveReader<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        if self.pos >= self.buf.len() {
+            self.next_element()?;
+        }
+        Ok(&self.buf[self.pos..])
+    }
+
+    fn consume(&mut self, amount: usize) {
+        self.pos = (self.pos + amount).min(self.buf.len());
+    }
+}
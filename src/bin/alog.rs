@@ -1,116 +1,772 @@
-use astra_logger_rs::formatter::{LogFormatter, Logs};
+use astra_logger_rs::analyzer::Analyzer;
+use astra_logger_rs::classifier::Classifier;
+use astra_logger_rs::exporter::{
+    delimiter_for, Column, CsvWriter, JsonMode, JsonWriter, STDOUT_PATH,
+};
+use astra_logger_rs::filter::LineFilter;
+use astra_logger_rs::follow::Follower;
+use astra_logger_rs::formatter::{LevelFilter, Logs};
+use astra_logger_rs::ingest::{
+    make_parser, merge_by_time, modified, parse_sources, split_label, Reading, Source,
+};
+use astra_logger_rs::multiline::{Joiner, MAX_EVENT_BYTES, MAX_EVENT_LINES};
+use astra_logger_rs::parser::{detect_format, LogFormat, LogParser};
+use astra_logger_rs::query::Query;
+use astra_logger_rs::report::Report;
+use astra_logger_rs::rules::RuleSet;
 use astra_logger_rs::scanner::LogStats;
+use astra_logger_rs::timerange::{parse_time, TimeRange};
 use astra_logger_rs::vizualizer::run_app;
-use clap::Parser;
+use astra_logger_rs::walker::{Walker, Warning};
+use chrono::Local;
+use clap::{Parser, Subcommand};
+use std::collections::HashMap;
 use std::fs::File;
-use std::io::{BufRead, BufReader};
-use std::path::PathBuf;
+use std::io::{IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::pin::pin;
+use tokio::sync::mpsc;
+
+// Путь `-` означает стандартный ввод
+const STDIN_PATH: &str = "-";
 
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None)]
 struct Args {
-    /// Путь к файлу или директории с логами
-    #[arg(short, long)]
+    /// Путь к файлу, директории или FIFO с логами; `-` — стандартный ввод
+    /// (читается и без -p, если ввод перенаправлен). Файлу или потоку можно
+    /// дать метку вместо пути: `-p host1=<(ssh host1 cat /var/log/syslog)`
+    #[arg(short, long, global = true)]
     paths: Vec<PathBuf>,
 
-    /// Уровень логов для фильтрации (info, warning, error, trace)
-    #[arg(short = 'l', long, default_value = "")]
+    /// Источник для записей из стандартного ввода (вместо пути к файлу)
+    #[arg(long, default_value = "stdin", global = true)]
+    label: String,
+
+    /// Уровень логов для фильтрации (emerg, alert, crit, error, warning, notice, info, debug, trace);
+    /// с суффиксом `+` — этот уровень и более важные, например `error+`
+    #[arg(short = 'l', long, default_value = "", global = true)]
     log_level: String,
 
-    /// Сохранение файла для удобного формата логов в json
+    /// Регулярное выражение для фильтрации строк до анализа; можно указать несколько раз
+    /// (строка проходит, если совпала хотя бы с одним)
+    #[arg(short = 'e', long, global = true)]
+    pattern: Vec<String>,
+
+    /// Оставлять строки, которые не совпали ни с одним --pattern
+    #[arg(short = 'v', long, requires = "pattern", global = true)]
+    invert: bool,
+
+    /// Не различать регистр в --pattern
+    #[arg(short = 'i', long, requires = "pattern", global = true)]
+    ignore_case: bool,
+
+    /// Только записи не раньше этого времени: `2024-03-22 11:53`, `11:53`, `2h ago`,
+    /// `yesterday`, `today`, `boot`
+    #[arg(long, global = true)]
+    since: Option<String>,
+
+    /// Только записи не позже этого времени (те же форматы, что у --since)
+    #[arg(long, global = true)]
+    until: Option<String>,
+
+    /// Запрос по полям записи: level, program, pid, host, file, msg, body, tag, time;
+    /// например `level>=warning and program=dbus-daemon and msg~"activat"`
+    #[arg(short = 'q', long, global = true)]
+    query: Option<String>,
+
+    /// Склеивать строки-продолжения (с отступом или без заголовка формата) с предыдущей записью:
+    /// трассировки стека, oops ядра, списки модулей Xorg
+    #[arg(short = 'm', long, global = true)]
+    multiline: bool,
+
+    /// Регулярное выражение начала записи для --multiline (вместо заголовка формата)
+    #[arg(long, requires = "multiline", global = true)]
+    event_start: Option<String>,
+
+    /// Наибольшее число строк в одной записи для --multiline
+    #[arg(long, requires = "multiline", default_value_t = MAX_EVENT_LINES, global = true)]
+    max_event_lines: usize,
+
+    /// Наибольший размер одной записи в байтах для --multiline
+    #[arg(long, requires = "multiline", default_value_t = MAX_EVENT_BYTES, global = true)]
+    max_event_bytes: usize,
+
+    /// Сохранение записей в json; `-` — стандартный вывод (для конвейеров)
     #[arg(short = 'j', long)]
     output_json: Option<String>,
 
+    /// Формат --output-json (по умолчанию ndjson для `.ndjson`, `.jsonl` и `-`, иначе json-pretty)
+    #[arg(long, value_enum, requires = "output_json")]
+    output_format: Option<JsonMode>,
+
+    /// Сохранение записей в csv (в tsv для `.tsv`); `-` — стандартный вывод
+    #[arg(long)]
+    output_csv: Option<String>,
+
+    /// Колонки --output-csv через запятую, в порядке вывода
+    #[arg(
+        long,
+        value_enum,
+        value_delimiter = ',',
+        requires = "output_csv",
+        default_value = "level,timestamp,host,program,pid,message,file"
+    )]
+    columns: Vec<Column>,
+
+    /// Разделять колонки --output-csv табуляцией (TSV)
+    #[arg(long, requires = "output_csv")]
+    tsv: bool,
+
+    /// Не писать строку с названиями колонок в --output-csv
+    #[arg(long, requires = "output_csv")]
+    no_header: bool,
+
+    /// Формат строк логов (по умолчанию определяется по первым строкам файла)
+    #[arg(short = 'f', long, value_enum, global = true)]
+    format: Option<LogFormat>,
+
+    /// Обходить директории рекурсивно
+    #[arg(short = 'r', long, global = true)]
+    recursive: bool,
+
+    /// Читать в директориях только файлы, подходящие под маску (например `*.log`); можно указать несколько раз
+    #[arg(long, global = true)]
+    include: Vec<String>,
+
+    /// Пропускать в директориях файлы и поддиректории, подходящие под маску (например `*.json`)
+    #[arg(long, global = true)]
+    exclude: Vec<String>,
+
+    /// Сколько файлов разбирать параллельно (по умолчанию — число ядер);
+    /// записи нескольких файлов упорядочиваются по времени события, большие
+    /// объёмы до слияния временно хранятся в TMPDIR
+    #[arg(short = 'J', long, global = true)]
+    jobs: Option<usize>,
+
     /// Запуск TUI
     #[arg(short = 't', long)]
     tui: bool,
+
+    /// В TUI показывать все источники одной лентой по времени, каждый своим цветом
+    #[arg(long, requires = "tui")]
+    merge: bool,
+
+    /// Продолжать следить за файлами и директориями (как `tail -F`), выход по Ctrl+C;
+    /// вместе с --tui новые записи появляются в интерфейсе
+    #[arg(short = 'F', long)]
+    follow: bool,
+
+    /// Файл с пользовательскими правилами классификации (TOML)
+    #[arg(long, global = true)]
+    rules: Option<PathBuf>,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Работа с правилами классификации
+    Rules {
+        #[command(subcommand)]
+        action: RulesCommand,
+    },
+    /// Отчёт по записям в одном HTML-файле без внешних ресурсов
+    Report {
+        /// Куда сохранить отчёт; `-` — стандартный вывод
+        #[arg(long)]
+        html: String,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum RulesCommand {
+    /// Показать, какое правило сработает для строки
+    Test {
+        /// Строка лога
+        line: String,
+    },
 }
 
 #[tokio::main]
 async fn main() {
-    let args = Args::parse();
+    let mut args = Args::parse();
 
-    let mut log_stats = LogStats::new();
-    let mut formatter = Logs::new();
+    let classifier = match &args.rules {
+        Some(path) => match RuleSet::load(path) {
+            Ok(rules) => Classifier::new(rules),
+            Err(err) => {
+                eprintln!("{:#}", err);
+                return;
+            }
+        },
+        None => Classifier::default(),
+    };
+
+    if let Some(Command::Rules {
+        action: RulesCommand::Test { line },
+    }) = &args.command
+    {
+        test_rules(&classifier.rules, line, args.format);
+        return;
+    }
+
+    // `journalctl | alog`: без путей читается перенаправленный ввод
+    if args.paths.is_empty() && !std::io::stdin().is_terminal() {
+        args.paths.push(PathBuf::from(STDIN_PATH));
+    }
 
     if args.paths.is_empty() {
         eprintln!("No paths provided");
         return;
     }
 
-    for path in &args.paths {
-        if path.is_file() {
-            analyze_file(path, &mut log_stats, &mut formatter, &args.log_level).await;
-        } else if path.is_dir() {
-            analyze_directory(path, &mut log_stats, &mut formatter, &args.log_level).await;
-        } else {
-            eprintln!("Invalid path: {}", path.display());
-            return;
+    // Метки из `-p label=path`; дальше пути используются без них
+    let mut labels: HashMap<PathBuf, String> = HashMap::new();
+    for path in &mut args.paths {
+        let (label, stripped) = split_label(path);
+        if let Some(label) = label {
+            if stripped.is_dir() {
+                eprintln!("Label {} can't be given to a directory", label);
+                return;
+            }
+            labels.insert(stripped.clone(), label);
+            *path = stripped;
         }
     }
 
-    let filter = match args.log_level.to_lowercase().as_str() {
-        "info" => Some(LogFormatter::Info),
-        "warning" => Some(LogFormatter::Warning),
-        "error" => Some(LogFormatter::Error),
-        "trace" => Some(LogFormatter::Trace),
+    if args.follow && args.paths.iter().any(|path| is_stdin(path)) {
+        eprintln!("--follow can't read standard input");
+        return;
+    }
+
+    let filter = match args.log_level.as_str() {
         "" => None,
-        _ => {
-            eprintln!("Invalid log level: {}", args.log_level);
+        level => match level.parse::<LevelFilter>() {
+            Ok(filter) => Some(filter),
+            Err(err) => {
+                eprintln!("{}", err);
+                return;
+            }
+        },
+    };
+
+    let lines = match LineFilter::new(&args.pattern, args.ignore_case, args.invert) {
+        Ok(lines) => lines,
+        Err(err) => {
+            eprintln!("Invalid pattern: {}", err);
+            return;
+        }
+    };
+
+    let now = Local::now();
+    let mut range = TimeRange::default();
+    for (value, bound) in [
+        (&args.since, &mut range.since),
+        (&args.until, &mut range.until),
+    ] {
+        if let Some(value) = value {
+            match parse_time(value, now) {
+                Ok(time) => *bound = Some(time),
+                Err(err) => {
+                    eprintln!("{}", err);
+                    return;
+                }
+            }
+        }
+    }
+    if let (Some(since), Some(until)) = (range.since, range.until) {
+        if since > until {
+            eprintln!("--since must not be later than --until");
             return;
         }
+    }
+
+    let query = match args
+        .query
+        .as_deref()
+        .map(|text| (text, Query::parse(text, now)))
+    {
+        None => None,
+        Some((_, Ok(query))) => Some(query),
+        Some((text, Err(err))) => {
+            eprintln!("Invalid query: {}", err);
+            eprintln!("  {}", text);
+            eprintln!("  {}", err.pointer());
+            return;
+        }
+    };
+
+    let joiner = match Joiner::new(
+        args.event_start.as_deref(),
+        args.max_event_lines,
+        args.max_event_bytes,
+    ) {
+        Ok(joiner) => joiner,
+        Err(err) => {
+            eprintln!("Invalid --event-start: {}", err);
+            return;
+        }
+    };
+
+    let reading = Reading {
+        classifier: &classifier,
+        format: args.format,
+        lines: &lines,
+        levels: filter,
+        query: query.as_ref(),
+        range,
+        joiner: args.multiline.then_some(&joiner),
+    };
+
+    let mut walker = match Walker::new(args.recursive, &args.include, &args.exclude) {
+        Ok(walker) => walker,
+        Err(err) => {
+            eprintln!("Invalid glob: {}", err);
+            return;
+        }
+    };
+
+    // Записи целиком хранятся только для TUI; в остальных случаях они сразу
+    // уходят в выходной файл, а в памяти остаются лишь счётчики
+    let mut log_stats = LogStats::new();
+    let mut formatter = Logs::new();
+    // С `-j -` стандартный вывод занят записями: статистика уходит в stderr
+    let report_path = match &args.command {
+        Some(Command::Report { html }) => Some(html.as_str()),
+        _ => None,
+    };
+    let stdout_outputs = [
+        args.output_json.as_deref(),
+        args.output_csv.as_deref(),
+        report_path,
+    ]
+    .into_iter()
+    .filter(|path| *path == Some(STDOUT_PATH))
+    .count();
+    let to_stdout = stdout_outputs > 0;
+    if stdout_outputs > 1 {
+        eprintln!("Only one output can be written to stdout (-)");
+        return;
+    }
+    if to_stdout && args.tui {
+        eprintln!("Writing entries to stdout (-) can't be combined with --tui");
+        return;
+    }
+    let mut json_writer = match &args.output_json {
+        Some(output_path) => {
+            let mode = args
+                .output_format
+                .unwrap_or_else(|| JsonMode::from_path(output_path));
+            match open_output(output_path) {
+                Some(out) => Some(JsonWriter::new(out, mode)),
+                None => return,
+            }
+        }
+        None => None,
+    };
+    let mut csv_writer = match &args.output_csv {
+        Some(output_path) => {
+            let delimiter = if args.tsv {
+                '\t'
+            } else {
+                delimiter_for(output_path)
+            };
+            match open_output(output_path) {
+                Some(out) => Some(CsvWriter::new(
+                    out,
+                    &args.columns,
+                    delimiter,
+                    !args.no_header,
+                )),
+                None => return,
+            }
+        }
+        None => None,
+    };
+
+    // Отчёт строится по всем записям, поэтому нужен конец ввода
+    if report_path.is_some() && (args.follow || args.tui) {
+        eprintln!("report can't be combined with --follow or --tui");
+        return;
+    }
+    let mut report = report_path.map(|_| Report::new());
+
+    // В --follow --tui записи передаются в интерфейс по каналу
+    let (mut live_sender, live_receiver) = if args.follow && args.tui {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        (Some(sender), Some(receiver))
+    } else {
+        (None, None)
     };
 
+    let mut analyzer = Analyzer::new(&classifier);
+    // --log-level и --query применяет Reading, до получателей доходят уже отобранные записи
+    analyzer.add_consumer(&mut log_stats);
+    if let Some(live_sender) = live_sender.as_mut() {
+        analyzer.add_consumer(live_sender);
+    } else if args.tui {
+        analyzer.add_consumer(&mut formatter);
+    }
+    if let Some(json_writer) = json_writer.as_mut() {
+        analyzer.add_consumer(json_writer);
+    }
+    if let Some(csv_writer) = csv_writer.as_mut() {
+        analyzer.add_consumer(csv_writer);
+    }
+    if let Some(report) = report.as_mut() {
+        analyzer.add_consumer(report);
+    }
+
+    if args.follow && args.tui {
+        let following = follow(
+            &args.paths,
+            &labels,
+            &mut walker,
+            &mut analyzer,
+            &reading,
+            false,
+        );
+        let mut tui = pin!(run_app(
+            Logs::new(),
+            LogStats::new(),
+            filter,
+            args.merge,
+            live_receiver,
+        ));
+
+        tokio::select! {
+            result = &mut tui => {
+                if let Err(err) = result {
+                    eprintln!("Error running TUI: {}", err);
+                }
+            }
+            result = following => {
+                // Слежение прервалось, но интерфейс остаётся открытым до выхода
+                let tui_result = tui.await;
+                if let Err(err) = result {
+                    eprintln!("Error following logs: {}", err);
+                }
+                if let Err(err) = tui_result {
+                    eprintln!("Error running TUI: {}", err);
+                }
+            }
+        }
+    } else if args.follow {
+        let following = follow(
+            &args.paths,
+            &labels,
+            &mut walker,
+            &mut analyzer,
+            &reading,
+            !to_stdout,
+        );
+        if let Err(err) = following.await {
+            if !err
+                .downcast_ref::<std::io::Error>()
+                .is_some_and(is_broken_pipe)
+            {
+                eprintln!("Error following logs: {}", err);
+            }
+        }
+    } else {
+        let mut sources = Vec::new();
+        for path in &args.paths {
+            let label = labels.get(path).cloned();
+            if is_stdin(path) {
+                sources.push(Source::Stdin(label.unwrap_or_else(|| args.label.clone())));
+            } else if path.is_file() || is_stream(path) {
+                sources.push(match label {
+                    Some(label) => Source::Labeled(path.clone(), label),
+                    None => Source::File(path.clone()),
+                });
+            } else if path.is_dir() {
+                sources.extend(walker.files(path).into_iter().map(Source::File));
+            } else {
+                eprintln!("Invalid path: {}", path.display());
+                return;
+            }
+        }
+
+        let jobs = args
+            .jobs
+            .unwrap_or_else(|| std::thread::available_parallelism().map_or(1, |jobs| jobs.get()));
+
+        if let Err(err) = analyze_sources(
+            &sources,
+            &reading,
+            &mut analyzer,
+            &mut walker.warnings,
+            jobs,
+        ) {
+            report_output_error(&err);
+            return;
+        }
+    }
+
+    if let Err(err) = analyzer.finish() {
+        report_output_error(&err);
+        return;
+    }
+    drop(analyzer);
+    print_warnings(&walker.warnings);
+
+    // Когда стандартный вывод занят записями или отчётом, сообщения идут в stderr
+    let saved = |what: &str, path: &str| {
+        if to_stdout {
+            eprintln!("{} saved to {}", what, path);
+        } else {
+            println!("{} saved to {}", what, path);
+        }
+    };
+
+    if let (Some(report), Some(report_path)) = (&report, report_path) {
+        let sources: Vec<String> = args
+            .paths
+            .iter()
+            .map(|path| path.display().to_string())
+            .collect();
+        let result = match open_output(report_path) {
+            Some(out) => report.write_html(out, &sources, Local::now()),
+            None => return,
+        };
+        if let Err(err) = result {
+            report_output_error(&err);
+            return;
+        }
+        if report_path != STDOUT_PATH {
+            saved("Report", report_path);
+        }
+    }
+
+    for output_path in [&args.output_json, &args.output_csv].into_iter().flatten() {
+        if output_path != STDOUT_PATH {
+            saved("Log entries", output_path);
+        }
+    }
+
     if args.tui {
-        if let Err(err) = run_app(formatter, log_stats, filter) {
+        // В режиме --follow интерфейс уже был открыт
+        if args.follow {
+            return;
+        }
+        if let Err(err) = run_app(formatter, log_stats, filter, args.merge, None).await {
             eprintln!("Error running TUI: {}", err);
         }
+    } else if to_stdout {
+        for line in log_stats.summary() {
+            eprintln!("{}", line);
+        }
     } else {
         log_stats.print_stats();
+    }
+}
 
-        if let Some(output_path) = args.output_json {
-            if let Err(err) = formatter.format_to_json(&output_path).await {
-                eprintln!("Error formatting log stats to JSON: {}", err);
-            } else {
-                println!("Log stats formatted to JSON and saved to {}", output_path);
+// Файл для записей или стандартный вывод для `-`; ошибка уже выведена
+fn open_output(output_path: &str) -> Option<Box<dyn Write>> {
+    if output_path == STDOUT_PATH {
+        return Some(Box::new(std::io::stdout()));
+    }
+    match File::create(output_path) {
+        Ok(file) => Some(Box::new(file)),
+        Err(err) => {
+            eprintln!("Failed to create {}: {}", output_path, err);
+            None
+        }
+    }
+}
+
+// Читатель конвейера закрылся раньше (`alog -j - | head`): это не ошибка
+fn is_broken_pipe(err: &std::io::Error) -> bool {
+    err.kind() == std::io::ErrorKind::BrokenPipe
+}
+
+fn report_output_error(err: &std::io::Error) {
+    if !is_broken_pipe(err) {
+        eprintln!("Error writing output: {}", err);
+    }
+}
+
+// Один источник читается потоково. Несколько источников разбираются
+// параллельно в очереди и сводятся в одну ленту по времени, поэтому результат
+// не зависит от --jobs.
+fn analyze_sources(
+    sources: &[Source],
+    reading: &Reading,
+    analyzer: &mut Analyzer<'_>,
+    warnings: &mut Vec<Warning>,
+    jobs: usize,
+) -> std::io::Result<()> {
+    if let [source] = sources {
+        // Ошибка чтения источника — предупреждение, ошибка записи результата — ошибка
+        let mut output = None;
+        let result = reading.read(source, |entry| match analyzer.dispatch(&entry) {
+            Ok(()) => Ok(()),
+            Err(err) => {
+                let kind = err.kind();
+                output = Some(err);
+                Err(kind.into())
             }
+        });
+        if let Some(err) = output {
+            return Err(err);
+        }
+        if let Err(err) = result {
+            warnings.push(source_warning(source, err));
         }
+        return Ok(());
     }
+
+    let parsed = parse_sources(reading, sources, jobs, |streams| -> std::io::Result<()> {
+        for entry in merge_by_time(streams) {
+            analyzer.dispatch(&entry)?;
+        }
+        Ok(())
+    });
+    let (merged, results) = match parsed {
+        Ok(parsed) => parsed,
+        Err(err) => {
+            eprintln!("Error spooling entries for merge: {}", err);
+            return Ok(());
+        }
+    };
+    merged?;
+    for (source, result) in sources.iter().zip(results) {
+        if let Err(err) = result {
+            warnings.push(source_warning(source, err));
+        }
+    }
+    Ok(())
+}
+
+fn source_warning(source: &Source, err: std::io::Error) -> Warning {
+    let path = match source {
+        Source::File(path) | Source::Labeled(path, _) => path.clone(),
+        Source::Stdin(label) => PathBuf::from(label),
+    };
+    Warning {
+        path,
+        reason: err.to_string(),
+    }
+}
+
+fn is_stdin(path: &Path) -> bool {
+    path.as_os_str() == STDIN_PATH
 }
 
-async fn analyze_file(
-    path: &PathBuf,
-    log_stats: &mut LogStats,
-    formatter: &mut Logs,
-    log_level: &str,
-) {
-    if let Ok(file) = File::open(path) {
-        let reader = BufReader::new(file);
-
-        for line in reader.lines() {
-            if let Ok(line) = line {
-                if log_level.is_empty() || line.contains(log_level) {
-                    formatter.analyze_log_line(&line, path.clone()).await;
-                    log_stats.analyze_log_line(&line).await;
+// FIFO и подстановка процессов (`<(ssh host cat /var/log/syslog)`)
+#[cfg(unix)]
+fn is_stream(path: &Path) -> bool {
+    use std::os::unix::fs::FileTypeExt;
+    std::fs::metadata(path).is_ok_and(|meta| {
+        let file_type = meta.file_type();
+        file_type.is_fifo() || file_type.is_char_device()
+    })
+}
+
+#[cfg(not(unix))]
+fn is_stream(_path: &Path) -> bool {
+    false
+}
+
+async fn follow(
+    paths: &[PathBuf],
+    labels: &HashMap<PathBuf, String>,
+    walker: &mut Walker,
+    analyzer: &mut Analyzer<'_>,
+    reading: &Reading<'_>,
+    // Печатать новые строки и сообщения о ротации (без TUI)
+    echo: bool,
+) -> eyre::Result<()> {
+    let mut follower = Follower::new(paths, walker)?;
+    // Парсер и попадание последней записи с датой в --since/--until для каждого файла
+    let mut parsers: HashMap<PathBuf, (Box<dyn LogParser>, bool)> = HashMap::new();
+
+    loop {
+        let batches = tokio::select! {
+            batches = follower.next_batches() => batches?,
+            _ = tokio::signal::ctrl_c() => return Ok(()),
+        };
+
+        for batch in batches {
+            if batch.rotated && echo {
+                eprintln!(
+                    "{} was rotated, reading from the start",
+                    batch.path.display()
+                );
+            }
+
+            let (parser, in_range) = parsers.entry(batch.path.clone()).or_insert_with(|| {
+                let parser = make_parser(&batch.lines, reading.format, modified(&batch.path));
+                (parser, false)
+            });
+
+            // События склеиваются только внутри пачки: продолжение, дописанное
+            // позже, станет отдельной записью
+            let lines = batch.lines.iter().cloned();
+            let events: Box<dyn Iterator<Item = String>> = match reading.joiner {
+                Some(joiner) => Box::new(joiner.join(lines, parser.as_ref())),
+                None => Box::new(lines),
+            };
+
+            let file_path = labels
+                .get(&batch.path)
+                .map_or_else(|| batch.path.clone(), PathBuf::from);
+            for line in events {
+                if reading.lines.matches(&line) {
+                    let entry = analyzer.entry(parser.as_ref(), &line, file_path.clone());
+                    if !reading.range.admit(entry.timestamp(), in_range) || !reading.accepts(&entry)
+                    {
+                        continue;
+                    }
+                    analyzer.dispatch(&entry)?;
+                    // Уже существовавшие строки только учитываются, новые выводятся
+                    if echo && !batch.initial {
+                        println!("{} {}", entry.level, entry.message);
+                    }
                 }
             }
         }
-    } else {
-        eprintln!("Failed to open log file: {}", path.display());
+
+        analyzer.flush()?;
+    }
+}
+
+// Пропущенные файлы перечисляются в конце, а не прерывают анализ
+fn print_warnings(warnings: &[Warning]) {
+    if warnings.is_empty() {
+        return;
+    }
+    eprintln!("Skipped {} path(s):", warnings.len());
+    for warning in warnings {
+        eprintln!("  {}", warning);
     }
 }
 
-async fn analyze_directory(
-    path: &PathBuf,
-    log_stats: &mut LogStats,
-    formatter: &mut Logs,
-    log_level: &str,
-) {
-    for entry in path.read_dir().expect("Failed to read directory") {
-        let entry = entry.expect("Failed to read entry");
-        let path = entry.path();
-        if path.is_file() {
-            analyze_file(&path, log_stats, formatter, log_level).await;
+fn test_rules(rules: &RuleSet, line: &str, format: Option<LogFormat>) {
+    let format = format.unwrap_or_else(|| detect_format(&[line]));
+    let parsed = format.parser().parse(line);
+
+    match rules.find_index(line) {
+        Some(index) => {
+            let rule = &rules.rules[index];
+            println!("Matched rule {} ({}): {}", index + 1, rule.name, rule.level);
+            if !rule.tags.is_empty() {
+                println!("Tags: {}", rule.tags.join(", "));
+            }
+        }
+        None => {
+            if rules.is_empty() {
+                println!("No rules loaded");
+            } else {
+                println!("No rule matched");
+            }
+            println!(
+                "Built-in classification ({:?}): {}",
+                format,
+                parsed.classify()
+            );
         }
     }
 }
@@ -0,0 +1,24 @@
+use crate::formatter::LogFormatter;
+use crate::parser::ParsedLine;
+use crate::rules::{Rule, RuleSet};
+
+// Создаётся один раз на запуск и используется для всех файлов:
+// все регулярные выражения правил скомпилированы заранее
+#[derive(Debug, Default)]
+pub struct Classifier {
+    pub rules: RuleSet,
+}
+
+impl Classifier {
+    pub fn new(rules: RuleSet) -> Self {
+        Classifier { rules }
+    }
+
+    // Пользовательские правила имеют приоритет над маркерами формата и ключевыми словами
+    pub fn classify(&self, line: &str, parsed: &ParsedLine) -> (LogFormatter, Option<&Rule>) {
+        match self.rules.find(line) {
+            Some(rule) => (rule.level, Some(rule)),
+            None => (parsed.classify(), None),
+        }
+    }
+}
@@ -0,0 +1,259 @@
+use crate::analyzer::Consumer;
+use crate::formatter::LogEntry;
+use clap::ValueEnum;
+use std::borrow::Cow;
+use std::io::{self, BufWriter, Write};
+
+// Путь `-` в --output-json означает стандартный вывод
+pub const STDOUT_PATH: &str = "-";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum JsonMode {
+    /// Одна компактная запись на строку (jq -c, Vector, Fluent Bit, grep)
+    Ndjson,
+    /// Один массив без пробелов и переводов строк
+    Json,
+    /// Один массив с отступами, как у Logs::format_to_json
+    JsonPretty,
+}
+
+impl JsonMode {
+    // Без --output-format: `.ndjson`, `.jsonl` и стандартный вывод — построчный
+    // формат, всё остальное — массив с отступами
+    pub fn from_path(path: &str) -> JsonMode {
+        if path == STDOUT_PATH || path.ends_with(".ndjson") || path.ends_with(".jsonl") {
+            JsonMode::Ndjson
+        } else {
+            JsonMode::JsonPretty
+        }
+    }
+}
+
+// Пишет записи сразу по мере поступления, не накапливая их в памяти
+pub struct JsonWriter<W: Write> {
+    out: BufWriter<W>,
+    mode: JsonMode,
+    written: usize,
+}
+
+impl<W: Write> JsonWriter<W> {
+    pub fn new(out: W, mode: JsonMode) -> Self {
+        JsonWriter {
+            out: BufWriter::new(out),
+            mode,
+            written: 0,
+        }
+    }
+
+    pub fn into_inner(self) -> io::Result<W> {
+        self.out.into_inner().map_err(|err| err.into_error())
+    }
+}
+
+impl<W: Write> Consumer for JsonWriter<W> {
+    fn consume(&mut self, entry: &LogEntry) -> io::Result<()> {
+        match self.mode {
+            JsonMode::Json => {
+                if self.written > 0 {
+                    self.out.write_all(b",")?;
+                } else {
+                    self.out.write_all(b"[")?;
+                }
+                serde_json::to_writer(&mut self.out, entry)?;
+            }
+            JsonMode::JsonPretty => {
+                self.out
+                    .write_all(if self.written == 0 { b"[\n" } else { b",\n" })?;
+                let json = serde_json::to_string_pretty(entry)?;
+                for (index, line) in json.lines().enumerate() {
+                    if index > 0 {
+                        self.out.write_all(b"\n")?;
+                    }
+                    write!(self.out, "  {}", line)?;
+                }
+            }
+            JsonMode::Ndjson => {
+                serde_json::to_writer(&mut self.out, entry)?;
+                self.out.write_all(b"\n")?;
+            }
+        }
+        self.written += 1;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        match self.mode {
+            JsonMode::Json => {
+                self.out
+                    .write_all(if self.written == 0 { b"[]\n" } else { b"]\n" })?
+            }
+            JsonMode::JsonPretty => {
+                self.out
+                    .write_all(if self.written == 0 { b"[]" } else { b"\n]" })?
+            }
+            JsonMode::Ndjson => {}
+        }
+        self.out.flush()
+    }
+}
+
+// Колонки CSV/TSV; порядок в файле — порядок в --columns
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Column {
+    /// Уровень записи
+    Level,
+    /// Время события (RFC 3339)
+    #[value(alias = "date")]
+    Timestamp,
+    /// Время чтения строки
+    Ingested,
+    Host,
+    Program,
+    Pid,
+    /// Исходная строка целиком
+    Message,
+    /// Текст сообщения без заголовка
+    Body,
+    /// Файл или метка источника
+    #[value(alias = "path")]
+    File,
+    /// Метки правила через `;`
+    Tags,
+}
+
+impl Column {
+    // Порядок колонок по умолчанию
+    pub const DEFAULT: [Column; 7] = [
+        Column::Level,
+        Column::Timestamp,
+        Column::Host,
+        Column::Program,
+        Column::Pid,
+        Column::Message,
+        Column::File,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Column::Level => "level",
+            Column::Timestamp => "timestamp",
+            Column::Ingested => "ingested",
+            Column::Host => "host",
+            Column::Program => "program",
+            Column::Pid => "pid",
+            Column::Message => "message",
+            Column::Body => "body",
+            Column::File => "file",
+            Column::Tags => "tags",
+        }
+    }
+
+    // Пустая строка, если в записи поля нет
+    fn value<'e>(&self, entry: &'e LogEntry) -> Cow<'e, str> {
+        match self {
+            Column::Level => entry.level.title().into(),
+            Column::Timestamp => entry.date.as_deref().unwrap_or_default().into(),
+            Column::Ingested => entry.ingested_at.as_str().into(),
+            Column::Host => entry.host.as_deref().unwrap_or_default().into(),
+            Column::Program => entry.program.as_deref().unwrap_or_default().into(),
+            Column::Pid => entry
+                .pid
+                .map(|pid| pid.to_string())
+                .unwrap_or_default()
+                .into(),
+            Column::Message => entry.message.as_str().into(),
+            Column::Body => entry.body.as_deref().unwrap_or(&entry.message).into(),
+            Column::File => entry.file_path.to_string_lossy(),
+            Column::Tags => entry.tags.join(";").into(),
+        }
+    }
+}
+
+// Разделитель по расширению: `.tsv` — табуляция, всё остальное — запятая
+pub fn delimiter_for(path: &str) -> char {
+    if path.ends_with(".tsv") {
+        '\t'
+    } else {
+        ','
+    }
+}
+
+// CSV/TSV с кавычками по RFC 4180: поле с разделителем, кавычкой или переводом
+// строки берётся в кавычки, кавычки внутри удваиваются. Как и JsonWriter,
+// пишет записи по мере поступления.
+pub struct CsvWriter<W: Write> {
+    out: BufWriter<W>,
+    columns: Vec<Column>,
+    delimiter: char,
+    // Заголовок пишется перед первой записью (или в finish, если записей нет)
+    header: bool,
+}
+
+impl<W: Write> CsvWriter<W> {
+    pub fn new(out: W, columns: &[Column], delimiter: char, header: bool) -> Self {
+        CsvWriter {
+            out: BufWriter::new(out),
+            columns: columns.to_vec(),
+            delimiter,
+            header,
+        }
+    }
+
+    pub fn into_inner(self) -> io::Result<W> {
+        self.out.into_inner().map_err(|err| err.into_error())
+    }
+
+    fn write_field(&mut self, field: &str) -> io::Result<()> {
+        if field.contains([self.delimiter, '"', '\r', '\n']) {
+            write!(self.out, "\"{}\"", field.replace('"', "\"\""))
+        } else {
+            self.out.write_all(field.as_bytes())
+        }
+    }
+
+    // RFC 4180 требует CRLF; TSV обычно читают построчно, там достаточно LF
+    fn write_record<'f>(&mut self, fields: impl Iterator<Item = Cow<'f, str>>) -> io::Result<()> {
+        for (index, field) in fields.enumerate() {
+            if index > 0 {
+                write!(self.out, "{}", self.delimiter)?;
+            }
+            self.write_field(&field)?;
+        }
+        self.out.write_all(if self.delimiter == '\t' {
+            b"\n"
+        } else {
+            b"\r\n"
+        })
+    }
+
+    fn write_header(&mut self) -> io::Result<()> {
+        if std::mem::take(&mut self.header) {
+            let names: Vec<&'static str> = self.columns.iter().map(Column::name).collect();
+            self.write_record(names.into_iter().map(Cow::from))?;
+        }
+        Ok(())
+    }
+}
+
+impl<W: Write> Consumer for CsvWriter<W> {
+    fn consume(&mut self, entry: &LogEntry) -> io::Result<()> {
+        self.write_header()?;
+        let columns = std::mem::take(&mut self.columns);
+        let result = self.write_record(columns.iter().map(|column| column.value(entry)));
+        self.columns = columns;
+        result
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        self.write_header()?;
+        self.out.flush()
+    }
+}
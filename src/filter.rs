@@ -0,0 +1,36 @@
+use regex::{RegexSet, RegexSetBuilder};
+
+// Фильтр строк по регулярным выражениям (--pattern); применяется до разбора.
+// Строка проходит, если совпала хотя бы с одним выражением, а с --invert —
+// если не совпала ни с одним. Без выражений проходят все строки.
+#[derive(Debug, Clone, Default)]
+pub struct LineFilter {
+    set: Option<RegexSet>,
+    invert: bool,
+}
+
+impl LineFilter {
+    pub fn new(
+        patterns: &[String],
+        ignore_case: bool,
+        invert: bool,
+    ) -> Result<LineFilter, regex::Error> {
+        if patterns.is_empty() {
+            return Ok(LineFilter::default());
+        }
+        let set = RegexSetBuilder::new(patterns)
+            .case_insensitive(ignore_case)
+            .build()?;
+        Ok(LineFilter {
+            set: Some(set),
+            invert,
+        })
+    }
+
+    pub fn matches(&self, line: &str) -> bool {
+        match &self.set {
+            Some(set) => set.is_match(line) != self.invert,
+            None => true,
+        }
+    }
+}
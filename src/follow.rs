@@ -0,0 +1,333 @@
+use crate::reader::Compression;
+use crate::walker::Walker;
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fs::{File, Metadata};
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+// Сколько байт читать из одного файла за раз, чтобы не держать в памяти весь файл
+const CHUNK_SIZE: u64 = 1 << 20;
+
+// Даже без событий от notify файлы перепроверяются с этим периодом
+// (copytruncate и сетевые ФС событий не присылают)
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+// Новые строки одного файла
+#[derive(Debug)]
+pub struct FollowBatch {
+    pub path: PathBuf,
+    pub lines: Vec<String>,
+    // Файл был пересоздан (logrotate) или обрезан (copytruncate)
+    pub rotated: bool,
+    // Строки, которые уже были в файле на момент запуска
+    pub initial: bool,
+}
+
+// Что остаётся у файла при переименовании и меняется при пересоздании
+type FileId = (u64, u64);
+
+#[cfg(unix)]
+fn file_id(meta: &Metadata) -> io::Result<FileId> {
+    use std::os::unix::fs::MetadataExt;
+    Ok((meta.dev(), meta.ino()))
+}
+
+// Без inode файл узнаётся по времени создания; обрезание на месте
+// по-прежнему видно по длине. Если ФС не хранит время создания,
+// следить за ротацией нельзя, и --follow завершается с ошибкой.
+#[cfg(not(unix))]
+fn file_id(meta: &Metadata) -> io::Result<FileId> {
+    let created = meta
+        .created()?
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_err(io::Error::other)?;
+    Ok((created.as_secs(), created.subsec_nanos() as u64))
+}
+
+struct Tail {
+    file: File,
+    id: FileId,
+    offset: u64,
+    // Размер файла при первом открытии во время запуска
+    initial_len: u64,
+    // Хвост без перевода строки: строка ещё дописывается
+    pending: Vec<u8>,
+}
+
+impl Tail {
+    fn open(path: &Path, startup: bool) -> io::Result<Tail> {
+        let file = File::open(path)?;
+        let meta = file.metadata()?;
+        Ok(Tail {
+            file,
+            id: file_id(&meta)?,
+            offset: 0,
+            initial_len: if startup { meta.len() } else { 0 },
+            pending: Vec::new(),
+        })
+    }
+
+    // Читает не больше CHUNK_SIZE байт; возвращает true, если данные ещё остались
+    fn read_lines(&mut self, lines: &mut Vec<String>) -> io::Result<bool> {
+        let len = self.file.metadata()?.len();
+        if len <= self.offset {
+            return Ok(false);
+        }
+
+        let mut buf = Vec::new();
+        self.file.seek(SeekFrom::Start(self.offset))?;
+        (&mut self.file).take(CHUNK_SIZE).read_to_end(&mut buf)?;
+        self.offset += buf.len() as u64;
+
+        self.pending.extend_from_slice(&buf);
+        if let Some(end) = self.pending.iter().rposition(|b| *b == b'\n') {
+            let rest = self.pending.split_off(end + 1);
+            let complete = std::mem::replace(&mut self.pending, rest);
+            lines.extend(String::from_utf8_lossy(&complete).lines().map(String::from));
+        }
+
+        Ok(self.offset < len)
+    }
+
+    // Недописанная строка отдаётся как есть, когда файл сменился
+    fn flush_pending(&mut self, lines: &mut Vec<String>) {
+        if !self.pending.is_empty() {
+            lines.push(String::from_utf8_lossy(&self.pending).into_owned());
+            self.pending.clear();
+        }
+    }
+}
+
+// Аналог `tail -F` для файлов и директорий: читает файлы с начала, затем
+// следит за дописыванием, ротацией и появлением новых файлов в директориях.
+// Директории обходятся через Walker с теми же фильтрами, что и без --follow.
+pub struct Follower<'a> {
+    files: BTreeSet<PathBuf>,
+    dirs: BTreeSet<PathBuf>,
+    walker: &'a mut Walker,
+    tails: BTreeMap<PathBuf, Tail>,
+    // Сколько уже прочитано из каждого файла (по устройству и inode): после
+    // ротации файл появляется в директории под новым именем и не читается заново
+    seen: HashMap<FileId, u64>,
+    events: mpsc::Receiver<()>,
+    // Watcher живёт, пока жив Follower
+    _watcher: RecommendedWatcher,
+    has_more: bool,
+    started: bool,
+}
+
+impl<'a> Follower<'a> {
+    pub fn new(paths: &[PathBuf], walker: &'a mut Walker) -> notify::Result<Follower<'a>> {
+        let (tx, events) = mpsc::channel(16);
+        let mut watcher =
+            notify::recommended_watcher(move |res: notify::Result<notify::Event>| {
+                if res.is_ok() {
+                    // Событие только будит цикл; если очередь полна, пробуждение уже запланировано
+                    let _ = tx.try_send(());
+                }
+            })?;
+
+        let mut files = BTreeSet::new();
+        let mut dirs = BTreeSet::new();
+        let mut watched = BTreeSet::new();
+        for path in paths {
+            if path.is_dir() {
+                dirs.insert(path.clone());
+                watched.insert(path.clone());
+            } else {
+                files.insert(path.clone());
+                // Следим за директорией, чтобы видеть пересоздание файла
+                let parent = match path.parent() {
+                    Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+                    _ => PathBuf::from("."),
+                };
+                watched.insert(parent);
+            }
+        }
+
+        for dir in &watched {
+            // С --recursive видны и новые поддиректории
+            let mode = if dirs.contains(dir) && walker.recursive() {
+                RecursiveMode::Recursive
+            } else {
+                RecursiveMode::NonRecursive
+            };
+            watcher.watch(dir, mode)?;
+        }
+
+        Ok(Follower {
+            files,
+            dirs,
+            walker,
+            tails: BTreeMap::new(),
+            seen: HashMap::new(),
+            events,
+            _watcher: watcher,
+            has_more: true,
+            started: false,
+        })
+    }
+
+    // Ждёт изменений и возвращает новые строки. Первый вызов отдаёт текущее
+    // содержимое файлов (порциями, если файлы большие).
+    pub async fn next_batches(&mut self) -> io::Result<Vec<FollowBatch>> {
+        loop {
+            if !self.has_more {
+                tokio::select! {
+                    _ = self.events.recv() => {}
+                    _ = tokio::time::sleep(POLL_INTERVAL) => {}
+                }
+            }
+
+            let batches = self.scan()?;
+            if !batches.is_empty() {
+                return Ok(batches);
+            }
+        }
+    }
+
+    fn candidates(&mut self) -> BTreeSet<PathBuf> {
+        let mut paths = self.files.clone();
+        self.walker.restart();
+        for dir in &self.dirs {
+            for path in self.walker.files(dir) {
+                // Сжатые ротации не дописываются, следить за ними незачем
+                if self.tails.contains_key(&path)
+                    || matches!(Compression::detect(&path), Ok(Compression::None))
+                {
+                    paths.insert(path);
+                }
+            }
+        }
+        paths
+    }
+
+    fn scan(&mut self) -> io::Result<Vec<FollowBatch>> {
+        let mut batches = Vec::new();
+        self.has_more = false;
+        // Файлы, которые сейчас есть на диске
+        let mut present = HashSet::new();
+
+        let candidates = self.candidates();
+        for path in candidates.iter().cloned() {
+            let mut lines = Vec::new();
+            let mut rotated = false;
+
+            let meta = match std::fs::metadata(&path) {
+                Ok(meta) => meta,
+                Err(_) => {
+                    // Файл переименован, а новый ещё не создан: дочитываем старый
+                    if let Some(tail) = self.tails.get_mut(&path) {
+                        while tail.read_lines(&mut lines)? {}
+                        if !lines.is_empty() {
+                            batches.push(FollowBatch {
+                                path,
+                                lines,
+                                rotated,
+                                initial: false,
+                            });
+                        }
+                    }
+                    continue;
+                }
+            };
+            let id = file_id(&meta)?;
+            present.insert(id);
+
+            if let Some(tail) = self.tails.get_mut(&path) {
+                if id != tail.id {
+                    // rename + create: дочитываем старый файл через открытый дескриптор
+                    while tail.read_lines(&mut lines)? {}
+                    tail.flush_pending(&mut lines);
+                    self.seen.insert(tail.id, tail.offset);
+                    self.tails.remove(&path);
+                    rotated = true;
+                } else if meta.len() < tail.offset {
+                    // copytruncate: файл обрезан на месте
+                    tail.offset = 0;
+                    tail.pending.clear();
+                    rotated = true;
+                }
+            }
+
+            if !self.tails.contains_key(&path) {
+                let moved = self
+                    .tails
+                    .iter()
+                    .find(|(_, tail)| tail.id == id)
+                    .map(|(old_path, _)| old_path.clone());
+
+                match moved {
+                    // Файл, за которым уже следим, переименован: продолжаем тот же хвост
+                    Some(old_path) => {
+                        if let Some(tail) = self.tails.remove(&old_path) {
+                            self.tails.insert(path.clone(), tail);
+                        }
+                    }
+                    None => match Tail::open(&path, !self.started) {
+                        Ok(mut tail) => {
+                            if let Some(offset) = self.seen.get(&tail.id) {
+                                tail.offset = *offset;
+                            }
+                            self.tails.insert(path.clone(), tail);
+                        }
+                        Err(_) => continue,
+                    },
+                }
+            }
+
+            let mut initial = false;
+            if let Some(tail) = self.tails.get_mut(&path) {
+                initial = tail.offset < tail.initial_len;
+                if tail.read_lines(&mut lines)? {
+                    self.has_more = true;
+                }
+                self.seen.insert(tail.id, tail.offset);
+            }
+
+            if !lines.is_empty() || rotated {
+                batches.push(FollowBatch {
+                    path,
+                    lines,
+                    rotated,
+                    initial,
+                });
+            }
+        }
+
+        // Файлы, пропавшие из директорий, дочитываются и закрываются, иначе
+        // место удалённых ротаций не освободится
+        let gone: Vec<PathBuf> = self
+            .tails
+            .keys()
+            .filter(|path| !candidates.contains(*path))
+            .cloned()
+            .collect();
+        for path in gone {
+            if let Some(mut tail) = self.tails.remove(&path) {
+                let mut lines = Vec::new();
+                while tail.read_lines(&mut lines)? {}
+                tail.flush_pending(&mut lines);
+                if !lines.is_empty() {
+                    batches.push(FollowBatch {
+                        path,
+                        lines,
+                        rotated: false,
+                        initial: false,
+                    });
+                }
+            }
+        }
+
+        // Смещения удалённых файлов больше не понадобятся
+        let tails: HashSet<FileId> = self.tails.values().map(|tail| tail.id).collect();
+        self.seen
+            .retain(|id, _| present.contains(id) || tails.contains(id));
+
+        self.started = true;
+        Ok(batches)
+    }
+}
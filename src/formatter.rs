@@ -1,40 +1,183 @@
-use chrono::{DateTime, Local};
-use regex::Regex;
+use crate::analyzer::{Analyzer, Consumer};
+use crate::classifier::Classifier;
+use crate::exporter::{JsonMode, JsonWriter};
+use crate::parser::PlainParser;
+use chrono::{DateTime, FixedOffset, Local};
 use serde::{Deserialize, Serialize};
 use std::fs::File;
-use std::io::Write;
 use std::path::PathBuf;
+use std::str::FromStr;
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+// Уровни упорядочены по возрастанию важности: Trace < Debug < ... < Emergency
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum LogFormatter {
+    Trace,
+    Debug,
     Info,
+    Notice,
     Warning,
     Error,
-    Trace,
+    Critical,
+    Alert,
+    Emergency,
+}
+
+impl LogFormatter {
+    // От самого важного к наименее важному
+    pub const ALL: [LogFormatter; 9] = [
+        LogFormatter::Emergency,
+        LogFormatter::Alert,
+        LogFormatter::Critical,
+        LogFormatter::Error,
+        LogFormatter::Warning,
+        LogFormatter::Notice,
+        LogFormatter::Info,
+        LogFormatter::Debug,
+        LogFormatter::Trace,
+    ];
+
+    // Числовая важность по RFC 5424 (0 — emerg, 7 — debug); у Trace её нет
+    pub fn severity(&self) -> Option<u8> {
+        match self {
+            LogFormatter::Emergency => Some(0),
+            LogFormatter::Alert => Some(1),
+            LogFormatter::Critical => Some(2),
+            LogFormatter::Error => Some(3),
+            LogFormatter::Warning => Some(4),
+            LogFormatter::Notice => Some(5),
+            LogFormatter::Info => Some(6),
+            LogFormatter::Debug => Some(7),
+            LogFormatter::Trace => None,
+        }
+    }
+
+    pub fn title(&self) -> &'static str {
+        match self {
+            LogFormatter::Emergency => "Emergency",
+            LogFormatter::Alert => "Alert",
+            LogFormatter::Critical => "Critical",
+            LogFormatter::Error => "Error",
+            LogFormatter::Warning => "Warning",
+            LogFormatter::Notice => "Notice",
+            LogFormatter::Info => "Info",
+            LogFormatter::Debug => "Debug",
+            LogFormatter::Trace => "Trace",
+        }
+    }
+
+    pub fn from_severity(severity: u8) -> Option<LogFormatter> {
+        LogFormatter::ALL
+            .into_iter()
+            .find(|level| level.severity() == Some(severity))
+    }
+}
+
+impl FromStr for LogFormatter {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "emerg" | "emergency" | "panic" => Ok(LogFormatter::Emergency),
+            "alert" => Ok(LogFormatter::Alert),
+            "crit" | "critical" | "fatal" => Ok(LogFormatter::Critical),
+            "err" | "error" => Ok(LogFormatter::Error),
+            "warn" | "warning" => Ok(LogFormatter::Warning),
+            "notice" => Ok(LogFormatter::Notice),
+            "info" | "informational" => Ok(LogFormatter::Info),
+            "debug" => Ok(LogFormatter::Debug),
+            "trace" => Ok(LogFormatter::Trace),
+            _ => Err(format!("Invalid log level: {}", s)),
+        }
+    }
+}
+
+// Фильтр по уровню: `error` — только ошибки, `error+` — ошибки и важнее
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LevelFilter {
+    pub level: LogFormatter,
+    pub and_above: bool,
+}
+
+impl LevelFilter {
+    pub fn matches(&self, level: LogFormatter) -> bool {
+        if self.and_above {
+            level >= self.level
+        } else {
+            level == self.level
+        }
+    }
+}
+
+impl FromStr for LevelFilter {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.strip_suffix('+') {
+            Some(level) => Ok(LevelFilter {
+                level: level.parse()?,
+                and_above: true,
+            }),
+            None => Ok(LevelFilter {
+                level: s.parse()?,
+                and_above: false,
+            }),
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct LogEntry {
     pub level: LogFormatter,
     pub message: String,
-    pub date: String,
+    // Время события из самой строки (RFC 3339); null, если его не удалось найти
+    #[serde(default)]
+    pub date: Option<String>,
+    // Время, когда строка была прочитана alog
+    #[serde(default)]
+    pub ingested_at: String,
+    #[serde(default)]
     pub file_path: PathBuf,
+    // Поля заголовка syslog: `astra dbus-daemon[383]: ...`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub host: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub program: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pid: Option<u32>,
+    // Текст сообщения без заголовка
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub body: Option<String>,
+    // Метки из сработавшего пользовательского правила
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
 }
 
 impl LogEntry {
     pub fn new(
         level: LogFormatter,
         message: String,
-        date: DateTime<Local>,
+        date: Option<DateTime<Local>>,
         file_path: PathBuf,
     ) -> Self {
         LogEntry {
             level,
             message,
-            date: date.to_rfc3339(),
+            date: date.map(|date| date.to_rfc3339()),
+            ingested_at: Local::now().to_rfc3339(),
             file_path,
+            host: None,
+            program: None,
+            pid: None,
+            body: None,
+            tags: Vec::new(),
         }
     }
+
+    pub fn timestamp(&self) -> Option<DateTime<FixedOffset>> {
+        self.date
+            .as_deref()
+            .and_then(|date| DateTime::parse_from_rfc3339(date).ok())
+    }
 }
 #[derive(Clone)]
 pub struct Logs {
@@ -42,6 +185,12 @@ pub struct Logs {
     pub entries: Vec<LogEntry>,
 }
 
+impl Default for Logs {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Logs {
     pub fn new() -> Logs {
         Logs {
@@ -51,51 +200,21 @@ impl Logs {
     }
 
     pub async fn analyze_log_line(&mut self, line: &str, file_path: PathBuf) {
-        self.total_messages += 1;
+        let classifier = Classifier::default();
+        let entry = Analyzer::new(&classifier).entry(&PlainParser, line, file_path);
+        self.push(entry);
+    }
 
-        let info_regex = Regex::new(r"(?i)info").unwrap();
-        let warning_regex = Regex::new(r"(?i)warning").unwrap();
-        let error_regex = Regex::new(r"(?i)error").unwrap();
-
-        let date = Local::now();
-
-        if info_regex.is_match(line) {
-            self.entries.push(LogEntry::new(
-                LogFormatter::Info,
-                line.to_string(),
-                date,
-                file_path,
-            ));
-        } else if warning_regex.is_match(line) {
-            self.entries.push(LogEntry::new(
-                LogFormatter::Warning,
-                line.to_string(),
-                date,
-                file_path,
-            ));
-        } else if error_regex.is_match(line) {
-            self.entries.push(LogEntry::new(
-                LogFormatter::Error,
-                line.to_string(),
-                date,
-                file_path,
-            ));
-        } else {
-            self.entries.push(LogEntry::new(
-                LogFormatter::Trace,
-                line.to_string(),
-                date,
-                file_path,
-            ));
-        }
+    pub fn push(&mut self, entry: LogEntry) {
+        self.total_messages += 1;
+        self.entries.push(entry);
     }
 
     pub async fn format_to_json(&self, output_path: &str) -> std::io::Result<()> {
-        let json = serde_json::to_string_pretty(&self.entries)?;
-
-        let mut file = File::create(output_path)?;
-        file.write_all(json.as_bytes())?;
-
-        Ok(())
+        let mut writer = JsonWriter::new(File::create(output_path)?, JsonMode::JsonPretty);
+        for entry in &self.entries {
+            writer.consume(entry)?;
+        }
+        writer.finish()
     }
 }
@@ -0,0 +1,410 @@
+use crate::analyzer::Analyzer;
+use crate::classifier::Classifier;
+use crate::filter::LineFilter;
+use crate::formatter::{LevelFilter, LogEntry};
+use crate::multiline::Joiner;
+use crate::parser::{detect_format, LogFormat, LogParser, SNIFF_LINES};
+use crate::query::Query;
+use crate::reader::{lossy_lines, open_log, open_reader, Compression};
+use crate::spool::{Spool, SpoolReader, SpoolWriter};
+use crate::timerange::TimeRange;
+use chrono::{DateTime, FixedOffset, Local};
+use std::cell::Cell;
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+// Откуда читаются строки
+#[derive(Debug, Clone, PartialEq)]
+pub enum Source {
+    File(PathBuf),
+    // Файл или поток (FIFO, `<(cmd)`) под меткой из `-p label=path`
+    Labeled(PathBuf, String),
+    // Стандартный ввод; метка подставляется вместо пути к файлу
+    Stdin(String),
+}
+
+impl Source {
+    // Поток строк, значение для file_path и опорное время для восстановления года
+    pub fn open(&self) -> io::Result<(Box<dyn BufRead + Send>, PathBuf, DateTime<Local>)> {
+        match self {
+            Source::File(path) => Ok((open_log(path)?, path.clone(), modified(path))),
+            Source::Labeled(path, label) => {
+                Ok((open_log(path)?, PathBuf::from(label), modified(path)))
+            }
+            Source::Stdin(label) => Ok((
+                open_reader(io::stdin())?,
+                PathBuf::from(label),
+                Local::now(),
+            )),
+        }
+    }
+
+    // Путь на диске, если источник — файл
+    pub fn file(&self) -> Option<&Path> {
+        match self {
+            Source::File(path) | Source::Labeled(path, _) => Some(path),
+            Source::Stdin(_) => None,
+        }
+    }
+}
+
+// Разделяет `label=path` из -p. Метка отделяется, только если сам аргумент
+// не существует, а часть после `=` — существующий путь или `-`.
+pub fn split_label(arg: &Path) -> (Option<String>, PathBuf) {
+    let exists = |path: &Path| path.as_os_str() == "-" || path.exists();
+    if exists(arg) {
+        return (None, arg.to_path_buf());
+    }
+    if let Some((label, path)) = arg.to_str().and_then(|arg| arg.split_once('=')) {
+        if !label.is_empty() && exists(Path::new(path)) {
+            return (Some(label.to_string()), PathBuf::from(path));
+        }
+    }
+    (None, arg.to_path_buf())
+}
+
+// Парсер для потока: формат из --format или по первым строкам
+pub fn make_parser(
+    head: &[String],
+    format: Option<LogFormat>,
+    reference: DateTime<Local>,
+) -> Box<dyn LogParser> {
+    let mut parser = format.unwrap_or_else(|| detect_format(head)).parser();
+    parser.prime(head, reference);
+    parser
+}
+
+pub fn modified(path: &Path) -> DateTime<Local> {
+    std::fs::metadata(path)
+        .and_then(|meta| meta.modified())
+        .map(DateTime::<Local>::from)
+        .unwrap_or_else(|_| Local::now())
+}
+
+// Файлы меньше этого размера читаются целиком, без поиска начала окна
+const SEEK_MIN_LEN: u64 = 1 << 20;
+
+// Двоичный поиск останавливается, когда окно меньше этого размера
+const SEEK_WINDOW: u64 = 64 << 10;
+
+// Сколько байт просматривается в поисках строки с датой в точке поиска
+const PROBE_LEN: u64 = 64 << 10;
+
+// Строки до первой ошибки чтения; ошибка сохраняется в `failed`
+fn until_error<'a>(
+    lines: impl Iterator<Item = io::Result<String>> + 'a,
+    failed: &'a Cell<Option<io::Error>>,
+) -> impl Iterator<Item = String> + 'a {
+    lines.map_while(|line| line.map_err(|err| failed.set(Some(err))).ok())
+}
+
+fn read_head(lines: &mut impl Iterator<Item = String>) -> Vec<String> {
+    let mut head = Vec::new();
+    while head
+        .iter()
+        .filter(|l: &&String| !l.trim().is_empty())
+        .count()
+        < SNIFF_LINES
+    {
+        match lines.next() {
+            Some(line) => head.push(line),
+            None => break,
+        }
+    }
+    head
+}
+
+// Время первой строки с датой после `offset` (незаконченная строка пропускается)
+fn probe_timestamp(
+    file: &mut File,
+    offset: u64,
+    parser: &dyn LogParser,
+) -> io::Result<Option<DateTime<Local>>> {
+    file.seek(SeekFrom::Start(offset))?;
+    let mut reader = BufReader::new(Read::by_ref(file).take(PROBE_LEN));
+    let mut line = Vec::new();
+    if offset > 0 {
+        reader.read_until(b'\n', &mut line)?;
+    }
+    loop {
+        line.clear();
+        if reader.read_until(b'\n', &mut line)? == 0 {
+            return Ok(None);
+        }
+        let text = String::from_utf8_lossy(&line);
+        if let Some(timestamp) = parser.parse(text.trim_end()).timestamp {
+            return Ok(Some(timestamp));
+        }
+    }
+}
+
+// Двоичный поиск по упорядоченному файлу: смещение начала строки, до которого
+// все записи раньше `since`. Строки после него всё равно проходят фильтр.
+// 0 — читать с начала: окно начинается в начале файла или время в точках
+// поиска убывает, то есть файл не упорядочен.
+pub fn seek_since(
+    file: &mut File,
+    parser: &dyn LogParser,
+    since: DateTime<Local>,
+) -> io::Result<u64> {
+    let (mut lo, mut hi) = (0, file.metadata()?.len());
+    let mut probes = vec![(0, probe_timestamp(file, 0, parser)?)];
+    while hi - lo > SEEK_WINDOW {
+        let mid = lo + (hi - lo) / 2;
+        let timestamp = probe_timestamp(file, mid, parser)?;
+        probes.push((mid, timestamp));
+        match timestamp {
+            Some(timestamp) if timestamp < since => lo = mid,
+            _ => hi = mid,
+        }
+    }
+    probes.sort_by_key(|(offset, _)| *offset);
+    let times: Vec<DateTime<Local>> = probes.into_iter().filter_map(|(_, time)| time).collect();
+    if lo == 0 || times.windows(2).any(|pair| pair[0] > pair[1]) {
+        return Ok(0);
+    }
+
+    file.seek(SeekFrom::Start(lo))?;
+    let mut partial = Vec::new();
+    let skipped = BufReader::new(Read::by_ref(file)).read_until(b'\n', &mut partial)?;
+    Ok(lo + skipped as u64)
+}
+
+// Настройки чтения, общие для всех источников
+pub struct Reading<'a> {
+    pub classifier: &'a Classifier,
+    pub format: Option<LogFormat>,
+    pub lines: &'a LineFilter,
+    pub levels: Option<LevelFilter>,
+    pub query: Option<&'a Query>,
+    pub range: TimeRange,
+    // Склейка строк-продолжений в одно событие (--multiline)
+    pub joiner: Option<&'a Joiner>,
+}
+
+impl Reading<'_> {
+    // Разбирает источник и отдаёт записи, прошедшие все фильтры.
+    // Первые строки читаются заранее, чтобы определить формат.
+    pub fn read<F>(&self, source: &Source, mut f: F) -> io::Result<()>
+    where
+        F: FnMut(LogEntry) -> io::Result<()>,
+    {
+        let (reader, file_path, reference) = source.open()?;
+        // Ошибка чтения заканчивает поток строк и возвращается после записей до неё
+        let failed = Cell::new(None);
+        let mut lines = until_error(lossy_lines(reader), &failed);
+        let head = read_head(&mut lines);
+        let parser = make_parser(&head, self.format, reference);
+
+        // В большом несжатом файле начало окна ищется двоичным поиском; если
+        // начало найдено, файл считается упорядоченным, и чтение заканчивается
+        // после --until. Иначе файл читается целиком
+        let seek = self
+            .seek_offset(source, parser.as_ref())?
+            .filter(|offset| *offset > 0);
+        let lines: Box<dyn Iterator<Item = String>> = match (seek, source.file()) {
+            (Some(offset), Some(path)) => {
+                let mut file = File::open(path)?;
+                file.seek(SeekFrom::Start(offset))?;
+                Box::new(until_error(lossy_lines(BufReader::new(file)), &failed))
+            }
+            _ => Box::new(head.into_iter().chain(lines)),
+        };
+        let sorted = seek.is_some();
+        let lines: Box<dyn Iterator<Item = String>> = match self.joiner {
+            Some(joiner) => Box::new(joiner.join(lines, parser.as_ref())),
+            None => lines,
+        };
+
+        let analyzer = Analyzer::new(self.classifier);
+        let mut in_range = false;
+
+        for line in lines {
+            if !self.lines.matches(&line) {
+                continue;
+            }
+            let entry = analyzer.entry(parser.as_ref(), &line, file_path.clone());
+            let timestamp = entry.timestamp();
+            if sorted && timestamp.is_some_and(|timestamp| self.range.is_after(&timestamp)) {
+                break;
+            }
+            if self.range.admit(timestamp, &mut in_range) && self.accepts(&entry) {
+                f(entry)?;
+            }
+        }
+        failed.take().map_or(Ok(()), Err)
+    }
+
+    // Проходит ли запись --log-level и --query. Analyzer, раздающий записи
+    // получателям, создаётся без фильтров, чтобы запись проверялась один раз
+    pub fn accepts(&self, entry: &LogEntry) -> bool {
+        self.levels.is_none_or(|filter| filter.matches(entry.level))
+            && self.query.is_none_or(|query| query.matches(entry))
+    }
+
+    fn seek_offset(&self, source: &Source, parser: &dyn LogParser) -> io::Result<Option<u64>> {
+        let (since, path) = match (self.range.since, source.file()) {
+            (Some(since), Some(path)) => (since, path),
+            _ => return Ok(None),
+        };
+        if Compression::detect(path)? != Compression::None {
+            return Ok(None);
+        }
+        let mut file = File::open(path)?;
+        if !file.metadata()?.is_file() || file.metadata()?.len() < SEEK_MIN_LEN {
+            return Ok(None);
+        }
+        seek_since(&mut file, parser, since).map(Some)
+    }
+}
+
+// Сколько записей всех источников держится в памяти до слияния
+const SPOOL_MEMORY: usize = 1 << 16;
+
+// Сколько источников сливается за раз; больших наборов сначала сливаются группы
+const MERGE_FAN_IN: usize = 32;
+
+// Результат чтения источника и его записи
+type ParsedSource = (io::Result<()>, Spool);
+
+// Разбирает источники на `jobs` потоках и передаёт `consume` их записи в
+// порядке источников. Поток читает один источник за раз и складывает записи
+// в Spool, поэтому открыто не больше `jobs` источников, а при слиянии — не
+// больше MERGE_FAN_IN временных файлов. Строки и записи, не прошедшие фильтры,
+// отбрасываются сразу. Ошибки чтения возвращаются в порядке источников, записи
+// до ошибки остаются; ошибка временных файлов возвращается вместо результата.
+pub fn parse_sources<R>(
+    reading: &Reading,
+    sources: &[Source],
+    jobs: usize,
+    consume: impl FnOnce(Vec<SpoolReader<'_>>) -> R,
+) -> io::Result<(R, Vec<io::Result<()>>)> {
+    let memory = AtomicUsize::new(SPOOL_MEMORY);
+    let next = AtomicUsize::new(0);
+    let parsed: Mutex<Vec<Option<ParsedSource>>> =
+        Mutex::new(sources.iter().map(|_| None).collect());
+
+    std::thread::scope(|scope| {
+        for _ in 0..jobs.clamp(1, sources.len().max(1)) {
+            scope.spawn(|| loop {
+                let index = next.fetch_add(1, Ordering::Relaxed);
+                let source = match sources.get(index) {
+                    Some(source) => source,
+                    None => break,
+                };
+
+                let mut spool = SpoolWriter::new(&memory);
+                let result = reading.read(source, |entry| spool.push(entry));
+                let parsed_source = match spool.finish() {
+                    Ok(spool) => (result, spool),
+                    Err(err) => (result.and(Err(err)), Spool::default()),
+                };
+
+                parsed.lock().unwrap()[index] = Some(parsed_source);
+            });
+        }
+    });
+
+    let (results, mut spools): (Vec<io::Result<()>>, Vec<Spool>) = parsed
+        .into_inner()
+        .unwrap()
+        .into_iter()
+        .map(|parsed| parsed.unwrap_or_else(|| (Ok(()), Spool::default())))
+        .unzip();
+
+    let failed = Mutex::new(None);
+    while spools.len() > MERGE_FAN_IN {
+        let mut groups = spools.into_iter().peekable();
+        let mut merged = Vec::new();
+        while groups.peek().is_some() {
+            let group: Vec<SpoolReader> = groups
+                .by_ref()
+                .take(MERGE_FAN_IN)
+                .map(|spool| spool.reader(&failed))
+                .collect();
+            let mut spool = SpoolWriter::new(&memory);
+            for entry in merge_by_time(group) {
+                spool.push(entry)?;
+            }
+            merged.push(spool.finish()?);
+        }
+        if let Some(err) = failed.lock().unwrap().take() {
+            return Err(err);
+        }
+        spools = merged;
+    }
+
+    let consumed = consume(
+        spools
+            .into_iter()
+            .map(|spool| spool.reader(&failed))
+            .collect(),
+    );
+    match failed.into_inner().unwrap() {
+        Some(err) => Err(err),
+        None => Ok((consumed, results)),
+    }
+}
+
+// Ключ сортировки: время записи или, если его нет, время предыдущей записи источника
+type MergeKey = (Option<DateTime<FixedOffset>>, usize);
+
+// k-way слияние источников по времени события. Каждый источник читается по
+// порядку, поэтому строки одного файла никогда не переставляются между собой;
+// при равном времени раньше идёт источник, указанный раньше. Записи без
+// времени остаются рядом с предыдущей записью своего источника. Из каждого
+// источника заранее берётся только одна запись, поэтому источниками служат
+// очереди parse_sources, а не прочитанные в память файлы.
+pub struct MergeByTime<I: Iterator<Item = LogEntry>> {
+    sources: Vec<I>,
+    // Время последней записи с датой в каждом источнике
+    last: Vec<Option<DateTime<FixedOffset>>>,
+    heads: Vec<Option<LogEntry>>,
+    heap: BinaryHeap<Reverse<MergeKey>>,
+}
+
+pub fn merge_by_time<S>(sources: Vec<S>) -> MergeByTime<S::IntoIter>
+where
+    S: IntoIterator<Item = LogEntry>,
+{
+    let sources: Vec<S::IntoIter> = sources.into_iter().map(IntoIterator::into_iter).collect();
+    let count = sources.len();
+    let mut merge = MergeByTime {
+        sources,
+        last: vec![None; count],
+        heads: (0..count).map(|_| None).collect(),
+        heap: BinaryHeap::with_capacity(count),
+    };
+    for index in 0..count {
+        merge.advance(index);
+    }
+    merge
+}
+
+impl<I: Iterator<Item = LogEntry>> MergeByTime<I> {
+    fn advance(&mut self, index: usize) {
+        if let Some(entry) = self.sources[index].next() {
+            if let Some(timestamp) = entry.timestamp() {
+                self.last[index] = Some(timestamp);
+            }
+            self.heap.push(Reverse((self.last[index], index)));
+            self.heads[index] = Some(entry);
+        }
+    }
+}
+
+impl<I: Iterator<Item = LogEntry>> Iterator for MergeByTime<I> {
+    type Item = LogEntry;
+
+    fn next(&mut self) -> Option<LogEntry> {
+        let Reverse((_, index)) = self.heap.pop()?;
+        let entry = self.heads[index].take();
+        self.advance(index);
+        entry
+    }
+}
@@ -0,0 +1,222 @@
+use crate::formatter::LogFormatter;
+use crate::parser::{LogFormat, LogParser, ParsedLine};
+use chrono::{DateTime, Local, TimeZone};
+use serde_json::{Map, Value};
+use std::fmt::Write;
+use std::io::{self, BufRead, Read};
+
+// Записи journald в формате `journalctl -o json`: по объекту JSON на строку.
+// Экспорт `journalctl -o export` перед разбором перекодируется в такие же строки.
+pub struct JournalParser;
+
+// Значение поля: строка, массив байтов (если значение не UTF-8) или массив
+// значений, если поле в записи повторяется (берётся первое)
+fn field(record: &Map<String, Value>, name: &str) -> Option<String> {
+    text(record.get(name)?)
+}
+
+fn text(value: &Value) -> Option<String> {
+    match value {
+        Value::String(text) => Some(text.clone()),
+        Value::Array(items) if items.iter().all(Value::is_u64) => {
+            let bytes: Vec<u8> = items
+                .iter()
+                .filter_map(Value::as_u64)
+                .map(|byte| byte as u8)
+                .collect();
+            Some(String::from_utf8_lossy(&bytes).into_owned())
+        }
+        Value::Array(items) => items.first().and_then(text),
+        _ => None,
+    }
+}
+
+// Строка как в `journalctl -o short`: `Mar 22 11:53:19 astra dbus-daemon[453]: ...`,
+// чтобы правила и поиск работали одинаково для journald и syslog
+fn short_line(
+    timestamp: Option<DateTime<Local>>,
+    host: Option<&str>,
+    program: Option<&str>,
+    pid: Option<u32>,
+    body: &str,
+) -> String {
+    let mut line = String::new();
+    if let Some(timestamp) = timestamp {
+        let _ = write!(line, "{} ", timestamp.format("%b %e %H:%M:%S"));
+    }
+    if let Some(host) = host {
+        let _ = write!(line, "{} ", host);
+    }
+    if let Some(program) = program {
+        line.push_str(program);
+        if let Some(pid) = pid {
+            let _ = write!(line, "[{}]", pid);
+        }
+        line.push_str(": ");
+    }
+    line.push_str(body);
+    line
+}
+
+impl LogParser for JournalParser {
+    fn format(&self) -> LogFormat {
+        LogFormat::Journal
+    }
+
+    fn matches(&self, line: &str) -> bool {
+        line.trim_start().starts_with('{')
+            && serde_json::from_str::<Map<String, Value>>(line).is_ok_and(|record| {
+                record.contains_key("MESSAGE") || record.contains_key("__REALTIME_TIMESTAMP")
+            })
+    }
+
+    fn parse<'a>(&self, line: &'a str) -> ParsedLine<'a> {
+        let record: Map<String, Value> = match serde_json::from_str(line) {
+            Ok(record) => record,
+            Err(_) => return ParsedLine::raw(line),
+        };
+
+        let body = field(&record, "MESSAGE").unwrap_or_default();
+        let host = field(&record, "_HOSTNAME");
+        // У сообщений ядра нет _COMM, только SYSLOG_IDENTIFIER=kernel
+        let program = field(&record, "_COMM").or_else(|| field(&record, "SYSLOG_IDENTIFIER"));
+        let pid = field(&record, "_PID")
+            .or_else(|| field(&record, "SYSLOG_PID"))
+            .and_then(|pid| pid.parse().ok());
+        let level = field(&record, "PRIORITY")
+            .and_then(|priority| priority.parse().ok())
+            .and_then(LogFormatter::from_severity);
+        // Микросекунды с начала эпохи
+        let timestamp = field(&record, "__REALTIME_TIMESTAMP")
+            .and_then(|micros| micros.parse().ok())
+            .and_then(|micros| Local.timestamp_micros(micros).single());
+
+        let message = short_line(timestamp, host.as_deref(), program.as_deref(), pid, &body);
+        ParsedLine {
+            body: body.into(),
+            level,
+            timestamp,
+            host: host.map(Into::into),
+            program: program.map(Into::into),
+            pid,
+            message: Some(message),
+        }
+    }
+}
+
+// Первая строка экспорта — служебное поле: `__CURSOR=...` или `__REALTIME_TIMESTAMP=...`
+pub fn is_export(head: &[u8]) -> bool {
+    let line = head.split(|byte| *byte == b'\n').next().unwrap_or_default();
+    match line.iter().position(|byte| *byte == b'=') {
+        Some(eq) => {
+            line.starts_with(b"__")
+                && line[..eq]
+                    .iter()
+                    .all(|byte| byte.is_ascii_uppercase() || byte.is_ascii_digit() || *byte == b'_')
+        }
+        None => false,
+    }
+}
+
+// Перекодирует `journalctl -o export` в JSON-строки для JournalParser.
+// В экспорте поля идут по одному на строку (`KEY=value`), записи разделены
+// пустой строкой; значение, которое не является текстом, записывается как
+// `KEY\n`, длина (u64 little-endian), сами байты и `\n`.
+pub struct ExportReader<R> {
+    inner: R,
+    // Текущая запись в виде JSON-строки
+    buf: Vec<u8>,
+    pos: usize,
+}
+
+impl<R: BufRead> ExportReader<R> {
+    pub fn new(inner: R) -> Self {
+        ExportReader {
+            inner,
+            buf: Vec::new(),
+            pos: 0,
+        }
+    }
+
+    // После вызова буфер пуст только в конце потока
+    fn next_record(&mut self) -> io::Result<()> {
+        self.buf.clear();
+        self.pos = 0;
+
+        let mut record = Map::new();
+        let mut line = Vec::new();
+        loop {
+            line.clear();
+            if self.inner.read_until(b'\n', &mut line)? == 0 {
+                break;
+            }
+            if line.last() == Some(&b'\n') {
+                line.pop();
+            }
+            if line.is_empty() {
+                if record.is_empty() {
+                    continue;
+                }
+                break;
+            }
+
+            let (name, value) = match line.iter().position(|byte| *byte == b'=') {
+                Some(eq) => (&line[..eq], line[eq + 1..].to_vec()),
+                None => (&line[..], self.read_binary()?),
+            };
+            record
+                .entry(String::from_utf8_lossy(name).into_owned())
+                .or_insert_with(|| Value::String(String::from_utf8_lossy(&value).into_owned()));
+        }
+
+        if !record.is_empty() {
+            serde_json::to_writer(&mut self.buf, &record)?;
+            self.buf.push(b'\n');
+        }
+        Ok(())
+    }
+
+    fn read_binary(&mut self) -> io::Result<Vec<u8>> {
+        let mut len = [0; 8];
+        self.inner.read_exact(&mut len)?;
+        let len = u64::from_le_bytes(len);
+
+        // Память выделяется по мере чтения, а не по заявленной длине:
+        // испорченный поток не должен заставить выделить гигабайты
+        let mut value = Vec::new();
+        Read::by_ref(&mut self.inner)
+            .take(len)
+            .read_to_end(&mut value)?;
+        let mut newline = [0; 1];
+        if (value.len() as u64) < len || self.inner.read(&mut newline)? == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "truncated binary field in journal export",
+            ));
+        }
+        Ok(value)
+    }
+}
+
+impl<R: BufRead> Read for ExportReader<R> {
+    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        let available = self.fill_buf()?;
+        let len = available.len().min(out.len());
+        out[..len].copy_from_slice(&available[..len]);
+        self.consume(len);
+        Ok(len)
+    }
+}
+
+impl<R: BufRead> BufRead for ExportReader<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        if self.pos >= self.buf.len() {
+            self.next_record()?;
+        }
+        Ok(&self.buf[self.pos..])
+    }
+
+    fn consume(&mut self, amount: usize) {
+        self.pos = (self.pos + amount).min(self.buf.len());
+    }
+}
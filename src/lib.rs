@@ -1,4 +1,21 @@
+pub mod analyzer;
+pub mod archive;
+pub mod classifier;
+pub mod exporter;
+pub mod filter;
+pub mod follow;
 pub mod formatter;
+pub mod ingest;
+pub mod journal;
+pub mod multiline;
+pub mod parser;
+pub mod query;
+pub mod reader;
+pub mod report;
+pub mod rules;
 pub mod scanner;
+pub mod spool;
 pub mod test;
+pub mod timerange;
 pub mod vizualizer;
+pub mod walker;
@@ -0,0 +1,93 @@
+use crate::parser::LogParser;
+use regex::Regex;
+use std::iter::Peekable;
+
+// Ограничения по умолчанию, чтобы одна «бесконечная» запись не съела память
+pub const MAX_EVENT_LINES: usize = 200;
+pub const MAX_EVENT_BYTES: usize = 64 << 10;
+
+// Склейка многострочных событий (--multiline): трассировки стека, oops ядра,
+// списки модулей Xorg. Строка продолжает предыдущее событие, если она или
+// текст после её заголовка начинается с отступа, или она не похожа на начало
+// записи — не совпадает со
+// `start`, а без него с заголовком формата. Событие, дошедшее до предела
+// строк или байт, закрывается, и следующая строка начинает новое.
+#[derive(Debug, Clone)]
+pub struct Joiner {
+    start: Option<Regex>,
+    // Строки с отступом всегда считаются продолжением
+    pub indent: bool,
+    pub max_lines: usize,
+    pub max_bytes: usize,
+}
+
+impl Joiner {
+    pub fn new(
+        start: Option<&str>,
+        max_lines: usize,
+        max_bytes: usize,
+    ) -> Result<Joiner, regex::Error> {
+        Ok(Joiner {
+            start: start.map(Regex::new).transpose()?,
+            indent: true,
+            max_lines: max_lines.max(1),
+            max_bytes,
+        })
+    }
+
+    fn is_continuation(&self, line: &str, parser: &dyn LogParser) -> bool {
+        if self.indent {
+            if line.starts_with([' ', '\t']) {
+                return true;
+            }
+            // Отступ после заголовка: `[    14.203] \tX.Org Video Driver: 24.1`
+            if parser.matches(line) && parser.parse(line).body.starts_with([' ', '\t']) {
+                return true;
+            }
+        }
+        match &self.start {
+            Some(start) => !start.is_match(line),
+            None => !parser.matches(line),
+        }
+    }
+
+    // События, склеенные из строк через `\n`
+    pub fn join<'a, I>(&'a self, lines: I, parser: &'a dyn LogParser) -> Events<'a, I::IntoIter>
+    where
+        I: IntoIterator<Item = String>,
+    {
+        Events {
+            joiner: self,
+            parser,
+            lines: lines.into_iter().peekable(),
+        }
+    }
+}
+
+pub struct Events<'a, I: Iterator<Item = String>> {
+    joiner: &'a Joiner,
+    parser: &'a dyn LogParser,
+    lines: Peekable<I>,
+}
+
+impl<I: Iterator<Item = String>> Iterator for Events<'_, I> {
+    type Item = String;
+
+    fn next(&mut self) -> Option<String> {
+        let mut event = self.lines.next()?;
+        let mut count = 1;
+        while let Some(line) = self.lines.peek() {
+            if count >= self.joiner.max_lines
+                || event.len() + 1 + line.len() > self.joiner.max_bytes
+                || !self.joiner.is_continuation(line, self.parser)
+            {
+                break;
+            }
+            event.push('\n');
+            event.push_str(line);
+            self.lines.next();
+            count += 1;
+        }
+        Some(event)
+    }
+}
@@ -0,0 +1,394 @@
+use crate::archive::ArchiveParser;
+use crate::formatter::{LogEntry, LogFormatter};
+use crate::journal::JournalParser;
+use chrono::{DateTime, Datelike, Duration, Local, NaiveDateTime, TimeZone};
+use clap::ValueEnum;
+use regex::Regex;
+use std::borrow::Cow;
+use std::path::Path;
+use std::sync::LazyLock;
+
+// Сколько первых непустых строк файла смотреть при определении формата
+pub const SNIFF_LINES: usize = 20;
+
+// Выражения заголовков компилируются один раз на процесс
+static SYSLOG_HEADER: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^(?P<ts>[A-Z][a-z]{2} [ \d]\d \d{2}:\d{2}:\d{2}) (?P<host>\S+) (?P<program>[^\s:\[]+)(?:\[(?P<pid>\d+)\])?: ?(?P<body>.*)$").unwrap()
+});
+static XORG_HEADER: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^\[\s*(?P<uptime>\d+\.\d+)\] ?(?P<body>.*)$").unwrap());
+static XORG_START_TIME: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"Log file: .*, Time: (?P<time>\w{3} \w{3} [ \d]?\d \d{2}:\d{2}:\d{2} \d{4})")
+        .unwrap()
+});
+static APP_HEADER: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^(?P<ts>\d{2}/\d{2}/\d{2} \d{2}:\d{2}:\d{2}) (?P<level>[A-Z]+)\s*:(?P<body>.*)$")
+        .unwrap()
+});
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum LogFormat {
+    /// BSD syslog: `Mar 22 11:53:15 astra systemd[1]: ...`
+    Syslog,
+    /// Xorg: `[    14.187] (II) ...`
+    Xorg,
+    /// `DD/MM/YY HH:MM:SS LEVEL :func: ...`
+    App,
+    /// journald: `journalctl -o json` или `journalctl -o export`
+    Journal,
+    /// Записи, сохранённые alog (`-j`): NDJSON или массив JSON
+    Archive,
+    /// Строка без известного заголовка
+    Plain,
+}
+
+impl LogFormat {
+    pub fn parser(&self) -> Box<dyn LogParser> {
+        match self {
+            LogFormat::Syslog => Box::new(SyslogParser::new()),
+            LogFormat::Xorg => Box::new(XorgParser::new()),
+            LogFormat::App => Box::new(AppParser::new()),
+            LogFormat::Journal => Box::new(JournalParser),
+            LogFormat::Archive => Box::new(ArchiveParser::new()),
+            LogFormat::Plain => Box::new(PlainParser),
+        }
+    }
+}
+
+// Результат разбора одной строки. Текстовые форматы ссылаются на части
+// строки, у JSON-форматов значения после разбора свои.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParsedLine<'a> {
+    pub body: Cow<'a, str>,
+    // Уровень по маркеру формата (`<warn>`, `(EE)`, колонка `INFO   :`)
+    pub level: Option<LogFormatter>,
+    // Время события; None, если в строке его нет или оно не распознано
+    pub timestamp: Option<DateTime<Local>>,
+    pub host: Option<Cow<'a, str>>,
+    pub program: Option<Cow<'a, str>>,
+    pub pid: Option<u32>,
+    // Текст записи, если сама строка им не является (объект JSON);
+    // используется вместо строки в message и при поиске правил
+    pub message: Option<String>,
+}
+
+impl<'a> ParsedLine<'a> {
+    pub fn raw(line: &'a str) -> Self {
+        ParsedLine {
+            body: Cow::Borrowed(line),
+            level: None,
+            timestamp: None,
+            host: None,
+            program: None,
+            pid: None,
+            message: None,
+        }
+    }
+
+    // Уровень по маркеру, а если его нет — по ключевым словам в тексте
+    pub fn classify(&self) -> LogFormatter {
+        self.level.unwrap_or_else(|| keyword_level(&self.body))
+    }
+}
+
+pub trait LogParser: Send + Sync {
+    fn format(&self) -> LogFormat;
+
+    // Настройка по первым строкам файла и его времени изменения
+    // (например, год для syslog или время старта для Xorg)
+    fn prime(&mut self, _head: &[String], _reference: DateTime<Local>) {}
+
+    // Похожа ли строка на формат парсера (используется при автоопределении)
+    fn matches(&self, line: &str) -> bool;
+
+    fn parse<'a>(&self, line: &'a str) -> ParsedLine<'a>;
+
+    // Готовая запись, если строка её уже содержит (архив alog); такие записи
+    // не классифицируются заново
+    fn stored_entry(&self, _line: &str, _file_path: &Path) -> Option<LogEntry> {
+        None
+    }
+}
+
+pub struct SyslogParser {
+    header: &'static Regex,
+    reference: DateTime<Local>,
+}
+
+impl SyslogParser {
+    pub fn new() -> Self {
+        SyslogParser {
+            header: &SYSLOG_HEADER,
+            reference: Local::now(),
+        }
+    }
+
+    // В syslog нет года: берётся год опорного времени (изменения файла),
+    // а если дата оказывается в будущем относительно него — предыдущий год
+    fn parse_timestamp(&self, ts: &str) -> Option<DateTime<Local>> {
+        let ts = ts.split_whitespace().collect::<Vec<_>>().join(" ");
+        let year = self.reference.year();
+        let at = |year: i32| {
+            NaiveDateTime::parse_from_str(&format!("{} {}", year, ts), "%Y %b %d %H:%M:%S")
+                .ok()
+                .and_then(|naive| Local.from_local_datetime(&naive).earliest())
+        };
+
+        let date = at(year)?;
+        if date > self.reference + Duration::days(1) {
+            at(year - 1)
+        } else {
+            Some(date)
+        }
+    }
+}
+
+impl Default for SyslogParser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LogParser for SyslogParser {
+    fn format(&self) -> LogFormat {
+        LogFormat::Syslog
+    }
+
+    fn prime(&mut self, _head: &[String], reference: DateTime<Local>) {
+        self.reference = reference;
+    }
+
+    fn matches(&self, line: &str) -> bool {
+        self.header.is_match(line)
+    }
+
+    fn parse<'a>(&self, line: &'a str) -> ParsedLine<'a> {
+        match self.header.captures(line) {
+            Some(caps) => ParsedLine {
+                body: caps.name("body").map_or("", |m| m.as_str()).into(),
+                level: caps.name("body").and_then(|m| syslog_tag_level(m.as_str())),
+                timestamp: caps
+                    .name("ts")
+                    .and_then(|m| self.parse_timestamp(m.as_str())),
+                host: caps.name("host").map(|m| m.as_str().into()),
+                program: caps.name("program").map(|m| m.as_str().into()),
+                pid: caps.name("pid").and_then(|m| m.as_str().parse().ok()),
+                message: None,
+            },
+            None => ParsedLine::raw(line),
+        }
+    }
+}
+
+pub struct XorgParser {
+    header: &'static Regex,
+    start_time: &'static Regex,
+    // Время запуска сервера (нулевая отметка аптайма)
+    anchor: Option<DateTime<Local>>,
+}
+
+impl XorgParser {
+    pub fn new() -> Self {
+        XorgParser {
+            header: &XORG_HEADER,
+            start_time: &XORG_START_TIME,
+            anchor: None,
+        }
+    }
+
+    // Строки Xorg содержат секунды с момента запуска. Строка с именем лог-файла
+    // содержит и отметку аптайма, и настенное время, по ним вычисляется старт.
+    fn find_anchor(&self, line: &str) -> Option<DateTime<Local>> {
+        let uptime = self.header.captures(line)?.name("uptime")?.as_str();
+        let time = self.start_time.captures(line)?.name("time")?.as_str();
+        let time = time.split_whitespace().collect::<Vec<_>>().join(" ");
+
+        let naive = NaiveDateTime::parse_from_str(&time, "%a %b %d %H:%M:%S %Y").ok()?;
+        let wall = Local.from_local_datetime(&naive).earliest()?;
+        Some(wall - uptime_duration(uptime)?)
+    }
+}
+
+fn uptime_duration(uptime: &str) -> Option<Duration> {
+    let seconds: f64 = uptime.parse().ok()?;
+    Some(Duration::microseconds(
+        (seconds * 1_000_000.0).round() as i64
+    ))
+}
+
+impl Default for XorgParser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LogParser for XorgParser {
+    fn format(&self) -> LogFormat {
+        LogFormat::Xorg
+    }
+
+    fn prime(&mut self, head: &[String], _reference: DateTime<Local>) {
+        self.anchor = head.iter().find_map(|line| self.find_anchor(line));
+    }
+
+    fn matches(&self, line: &str) -> bool {
+        self.header.is_match(line)
+    }
+
+    fn parse<'a>(&self, line: &'a str) -> ParsedLine<'a> {
+        match self.header.captures(line) {
+            Some(caps) => ParsedLine {
+                body: caps.name("body").map_or("", |m| m.as_str()).into(),
+                level: caps
+                    .name("body")
+                    .and_then(|m| xorg_marker_level(m.as_str())),
+                timestamp: self
+                    .anchor
+                    .zip(
+                        caps.name("uptime")
+                            .and_then(|m| uptime_duration(m.as_str())),
+                    )
+                    .map(|(anchor, uptime)| anchor + uptime),
+                ..ParsedLine::raw(line)
+            },
+            // Строки-продолжения (с отступом табуляцией) заголовка не имеют
+            None => ParsedLine::raw(line.trim()),
+        }
+    }
+}
+
+pub struct AppParser {
+    header: &'static Regex,
+}
+
+impl AppParser {
+    pub fn new() -> Self {
+        AppParser {
+            header: &APP_HEADER,
+        }
+    }
+}
+
+impl Default for AppParser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LogParser for AppParser {
+    fn format(&self) -> LogFormat {
+        LogFormat::App
+    }
+
+    fn matches(&self, line: &str) -> bool {
+        self.header.is_match(line)
+    }
+
+    fn parse<'a>(&self, line: &'a str) -> ParsedLine<'a> {
+        match self.header.captures(line) {
+            Some(caps) => ParsedLine {
+                body: caps.name("body").map_or("", |m| m.as_str()).into(),
+                level: caps.name("level").and_then(|m| m.as_str().parse().ok()),
+                timestamp: caps.name("ts").and_then(|m| {
+                    NaiveDateTime::parse_from_str(m.as_str(), "%d/%m/%y %H:%M:%S")
+                        .ok()
+                        .and_then(|naive| Local.from_local_datetime(&naive).earliest())
+                }),
+                ..ParsedLine::raw(line)
+            },
+            None => ParsedLine::raw(line),
+        }
+    }
+}
+
+pub struct PlainParser;
+
+impl LogParser for PlainParser {
+    fn format(&self) -> LogFormat {
+        LogFormat::Plain
+    }
+
+    fn matches(&self, _line: &str) -> bool {
+        true
+    }
+
+    fn parse<'a>(&self, line: &'a str) -> ParsedLine<'a> {
+        ParsedLine::raw(line)
+    }
+}
+
+// Тег уровня в начале сообщения: `<info>  [1715620992.6898] ...` (NetworkManager и др.)
+fn syslog_tag_level(body: &str) -> Option<LogFormatter> {
+    let rest = body.trim_start().strip_prefix('<')?;
+    let (tag, _) = rest.split_once('>')?;
+    tag.parse().ok()
+}
+
+// Маркеры Xorg: (EE) error, (WW) warning, (!!) notice, (II)/(--)/(==)/(**)/(++) — информация
+fn xorg_marker_level(body: &str) -> Option<LogFormatter> {
+    let marker = body.get(..4)?;
+    match marker {
+        "(EE)" => Some(LogFormatter::Error),
+        "(WW)" => Some(LogFormatter::Warning),
+        "(!!)" => Some(LogFormatter::Notice),
+        "(II)" | "(--)" | "(==)" | "(**)" | "(++)" => Some(LogFormatter::Info),
+        _ => None,
+    }
+}
+
+// Запасной вариант: поиск названий уровней как отдельных слов. Если слов
+// несколько, побеждает самый важный уровень ("info ... error" — это Error).
+pub fn keyword_level(text: &str) -> LogFormatter {
+    text.split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .filter_map(|word| match word.to_ascii_lowercase().as_str() {
+            "emerg" | "emergency" | "panic" => Some(LogFormatter::Emergency),
+            "alert" => Some(LogFormatter::Alert),
+            "crit" | "critical" | "fatal" => Some(LogFormatter::Critical),
+            "err" | "error" => Some(LogFormatter::Error),
+            "warn" | "warning" => Some(LogFormatter::Warning),
+            "notice" => Some(LogFormatter::Notice),
+            "info" => Some(LogFormatter::Info),
+            "debug" => Some(LogFormatter::Debug),
+            _ => None,
+        })
+        .max()
+        .unwrap_or(LogFormatter::Trace)
+}
+
+// Определяет формат по первым строкам файла. Побеждает парсер, которому
+// подошло больше половины непустых строк, иначе строки считаются Plain.
+pub fn detect_format<S: AsRef<str>>(lines: &[S]) -> LogFormat {
+    let candidates: [Box<dyn LogParser>; 5] = [
+        Box::new(SyslogParser::new()),
+        Box::new(XorgParser::new()),
+        Box::new(AppParser::new()),
+        Box::new(JournalParser),
+        Box::new(ArchiveParser::new()),
+    ];
+
+    let sample: Vec<&str> = lines
+        .iter()
+        .map(|l| l.as_ref())
+        .filter(|l| !l.trim().is_empty())
+        .take(SNIFF_LINES)
+        .collect();
+
+    if sample.is_empty() {
+        return LogFormat::Plain;
+    }
+
+    let mut best = (LogFormat::Plain, 0);
+    for parser in &candidates {
+        let hits = sample.iter().filter(|l| parser.matches(l)).count();
+        if hits > best.1 {
+            best = (parser.format(), hits);
+        }
+    }
+
+    if best.1 * 2 > sample.len() {
+        best.0
+    } else {
+        LogFormat::Plain
+    }
+}
@@ -0,0 +1,474 @@
+use crate::formatter::{LogEntry, LogFormatter};
+use crate::timerange::parse_time;
+use chrono::{DateTime, Local};
+use regex::Regex;
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::fmt;
+use std::str::FromStr;
+
+// Символы, которые не могут входить в слово без кавычек
+const SPECIAL: &str = "()\"'=!~<>";
+
+const FIELDS: &str = "level, program, pid, host, file, msg, body, tag, time";
+
+// Ошибка разбора запроса; колонка считается в символах, начиная с 1
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QueryError {
+    pub column: usize,
+    pub message: String,
+}
+
+impl QueryError {
+    fn new(column: usize, message: impl Into<String>) -> Self {
+        QueryError {
+            column,
+            message: message.into(),
+        }
+    }
+
+    // Строка с `^` под местом ошибки, для вывода под текстом запроса
+    pub fn pointer(&self) -> String {
+        format!("{}^", " ".repeat(self.column.saturating_sub(1)))
+    }
+}
+
+impl fmt::Display for QueryError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "column {}: {}", self.column, self.message)
+    }
+}
+
+impl std::error::Error for QueryError {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Op {
+    Eq,
+    Ne,
+    Match,
+    NotMatch,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl Op {
+    fn symbol(&self) -> &'static str {
+        match self {
+            Op::Eq => "=",
+            Op::Ne => "!=",
+            Op::Match => "~",
+            Op::NotMatch => "!~",
+            Op::Lt => "<",
+            Op::Le => "<=",
+            Op::Gt => ">",
+            Op::Ge => ">=",
+        }
+    }
+
+    fn is_regex(&self) -> bool {
+        matches!(self, Op::Match | Op::NotMatch)
+    }
+
+    fn is_ordering(&self) -> bool {
+        matches!(self, Op::Lt | Op::Le | Op::Gt | Op::Ge)
+    }
+
+    fn compare(&self, ordering: Ordering) -> bool {
+        match self {
+            Op::Eq => ordering == Ordering::Equal,
+            Op::Ne => ordering != Ordering::Equal,
+            Op::Lt => ordering == Ordering::Less,
+            Op::Le => ordering != Ordering::Greater,
+            Op::Gt => ordering == Ordering::Greater,
+            Op::Ge => ordering != Ordering::Less,
+            Op::Match | Op::NotMatch => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Word(String),
+    // Строка в кавычках: никогда не считается ключевым словом
+    Text(String),
+    Op(Op),
+    Open,
+    Close,
+    End,
+}
+
+impl Token {
+    fn describe(&self) -> String {
+        match self {
+            Token::Word(word) => format!("'{}'", word),
+            Token::Text(text) => format!("\"{}\"", text),
+            Token::Op(op) => format!("'{}'", op.symbol()),
+            Token::Open => "'('".to_string(),
+            Token::Close => "')'".to_string(),
+            Token::End => "end of query".to_string(),
+        }
+    }
+}
+
+fn tokenize(text: &str) -> Result<Vec<(usize, Token)>, QueryError> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let column = i + 1;
+        let next = chars.get(i + 1).copied();
+        let (token, len) = match chars[i] {
+            c if c.is_whitespace() => {
+                i += 1;
+                continue;
+            }
+            '(' => (Token::Open, 1),
+            ')' => (Token::Close, 1),
+            '=' if next == Some('=') => (Token::Op(Op::Eq), 2),
+            '=' => (Token::Op(Op::Eq), 1),
+            '~' => (Token::Op(Op::Match), 1),
+            '!' if next == Some('=') => (Token::Op(Op::Ne), 2),
+            '!' if next == Some('~') => (Token::Op(Op::NotMatch), 2),
+            '!' => return Err(QueryError::new(column, "expected '=' or '~' after '!'")),
+            '<' if next == Some('=') => (Token::Op(Op::Le), 2),
+            '<' => (Token::Op(Op::Lt), 1),
+            '>' if next == Some('=') => (Token::Op(Op::Ge), 2),
+            '>' => (Token::Op(Op::Gt), 1),
+            quote @ ('"' | '\'') => {
+                // Внутри кавычек экранируются только кавычки и `\`; остальные `\`
+                // остаются как есть, чтобы работали регулярные выражения вроде `\d+`
+                let mut value = String::new();
+                let mut end = i + 1;
+                loop {
+                    match chars.get(end) {
+                        None => return Err(QueryError::new(column, "unterminated string")),
+                        Some(c) if *c == quote => break,
+                        Some('\\') if matches!(chars.get(end + 1), Some('"' | '\'' | '\\')) => {
+                            value.push(chars[end + 1]);
+                            end += 2;
+                        }
+                        Some(c) => {
+                            value.push(*c);
+                            end += 1;
+                        }
+                    }
+                }
+                (Token::Text(value), end + 1 - i)
+            }
+            _ => {
+                let len = chars[i..]
+                    .iter()
+                    .take_while(|c| !c.is_whitespace() && !SPECIAL.contains(**c))
+                    .count();
+                (Token::Word(chars[i..i + len].iter().collect()), len)
+            }
+        };
+        tokens.push((column, token));
+        i += len;
+    }
+
+    tokens.push((chars.len() + 1, Token::End));
+    Ok(tokens)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Field {
+    Level,
+    Program,
+    Pid,
+    Host,
+    File,
+    Message,
+    Body,
+    Tag,
+    Time,
+}
+
+impl Field {
+    fn from_name(name: &str) -> Option<Field> {
+        match name.to_lowercase().as_str() {
+            "level" => Some(Field::Level),
+            "program" | "prog" => Some(Field::Program),
+            "pid" => Some(Field::Pid),
+            "host" => Some(Field::Host),
+            "file" | "source" | "path" => Some(Field::File),
+            "msg" | "message" => Some(Field::Message),
+            "body" => Some(Field::Body),
+            "tag" | "tags" => Some(Field::Tag),
+            "time" | "date" => Some(Field::Time),
+            _ => None,
+        }
+    }
+
+    // Поля, которые сравниваются как строки
+    fn is_text(&self) -> bool {
+        !matches!(self, Field::Level | Field::Pid | Field::Time)
+    }
+
+    // Значения строкового поля записи; у tag их может быть несколько, у program — ни одного
+    fn texts<'e>(&self, entry: &'e LogEntry) -> Vec<Cow<'e, str>> {
+        match self {
+            Field::Program => entry
+                .program
+                .as_deref()
+                .map(Cow::from)
+                .into_iter()
+                .collect(),
+            Field::Host => entry.host.as_deref().map(Cow::from).into_iter().collect(),
+            Field::File => vec![entry.file_path.to_string_lossy()],
+            Field::Message => vec![Cow::from(entry.message.as_str())],
+            Field::Body => vec![Cow::from(entry.body.as_deref().unwrap_or(&entry.message))],
+            Field::Tag => entry
+                .tags
+                .iter()
+                .map(|tag| Cow::from(tag.as_str()))
+                .collect(),
+            Field::Level | Field::Pid | Field::Time => Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Value {
+    Level(LogFormatter),
+    Pid(u32),
+    Time(DateTime<Local>),
+    Text(String),
+    Regex(Regex),
+}
+
+#[derive(Debug, Clone)]
+struct Condition {
+    field: Field,
+    op: Op,
+    value: Value,
+}
+
+impl Condition {
+    // Если поля в записи нет, выполняются только `!=` и `!~`
+    fn matches(&self, entry: &LogEntry) -> bool {
+        let negated = matches!(self.op, Op::Ne | Op::NotMatch);
+        match &self.value {
+            Value::Level(level) => self.op.compare(entry.level.cmp(level)),
+            Value::Pid(pid) => entry
+                .pid
+                .map_or(negated, |value| self.op.compare(value.cmp(pid))),
+            Value::Time(time) => entry.timestamp().map_or(negated, |value| {
+                self.op.compare(value.cmp(&time.fixed_offset()))
+            }),
+            Value::Text(text) => {
+                let found = self.field.texts(entry).iter().any(|value| value == text);
+                found != negated
+            }
+            Value::Regex(regex) => {
+                let found = self
+                    .field
+                    .texts(entry)
+                    .iter()
+                    .any(|value| regex.is_match(value));
+                found != negated
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Expr {
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Condition(Condition),
+}
+
+impl Expr {
+    fn matches(&self, entry: &LogEntry) -> bool {
+        match self {
+            Expr::And(left, right) => left.matches(entry) && right.matches(entry),
+            Expr::Or(left, right) => left.matches(entry) || right.matches(entry),
+            Expr::Not(expr) => !expr.matches(entry),
+            Expr::Condition(condition) => condition.matches(entry),
+        }
+    }
+}
+
+// Рекурсивный спуск: or < and < not < условие или скобки
+struct QueryParser {
+    tokens: Vec<(usize, Token)>,
+    pos: usize,
+    now: DateTime<Local>,
+}
+
+impl QueryParser {
+    fn next(&mut self) -> (usize, Token) {
+        let token = self.tokens[self.pos].clone();
+        if token.1 != Token::End {
+            self.pos += 1;
+        }
+        token
+    }
+
+    fn keyword(&mut self, keyword: &str) -> bool {
+        let found = matches!(&self.tokens[self.pos].1, Token::Word(word) if word.eq_ignore_ascii_case(keyword));
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn or(&mut self) -> Result<Expr, QueryError> {
+        let mut left = self.and()?;
+        while self.keyword("or") {
+            left = Expr::Or(Box::new(left), Box::new(self.and()?));
+        }
+        Ok(left)
+    }
+
+    fn and(&mut self) -> Result<Expr, QueryError> {
+        let mut left = self.not()?;
+        while self.keyword("and") {
+            left = Expr::And(Box::new(left), Box::new(self.not()?));
+        }
+        Ok(left)
+    }
+
+    fn not(&mut self) -> Result<Expr, QueryError> {
+        if self.keyword("not") {
+            return Ok(Expr::Not(Box::new(self.not()?)));
+        }
+        self.primary()
+    }
+
+    fn primary(&mut self) -> Result<Expr, QueryError> {
+        match self.next() {
+            (column, Token::Open) => {
+                let expr = self.or()?;
+                match self.next() {
+                    (_, Token::Close) => Ok(expr),
+                    (close, token) => Err(QueryError::new(
+                        close,
+                        format!(
+                            "expected ')' to close '(' at column {}, found {}",
+                            column,
+                            token.describe()
+                        ),
+                    )),
+                }
+            }
+            (column, Token::Word(name)) => self.condition(column, &name).map(Expr::Condition),
+            (column, token) => Err(QueryError::new(
+                column,
+                format!("expected a field ({}), found {}", FIELDS, token.describe()),
+            )),
+        }
+    }
+
+    fn condition(&mut self, column: usize, name: &str) -> Result<Condition, QueryError> {
+        let field = Field::from_name(name).ok_or_else(|| {
+            QueryError::new(
+                column,
+                format!("unknown field '{}', expected one of: {}", name, FIELDS),
+            )
+        })?;
+
+        let (op_column, op) = match self.next() {
+            (op_column, Token::Op(op)) => (op_column, op),
+            (op_column, token) => {
+                return Err(QueryError::new(
+                    op_column,
+                    format!(
+                        "expected an operator (=, !=, ~, !~, <, <=, >, >=) after '{}', found {}",
+                        name,
+                        token.describe()
+                    ),
+                ))
+            }
+        };
+        if (op.is_regex() && !field.is_text()) || (op.is_ordering() && field.is_text()) {
+            return Err(QueryError::new(
+                op_column,
+                format!("operator '{}' can't be used with '{}'", op.symbol(), name),
+            ));
+        }
+
+        let (value_column, text) = match self.next() {
+            (value_column, Token::Word(text) | Token::Text(text)) => (value_column, text),
+            (value_column, token) => {
+                return Err(QueryError::new(
+                    value_column,
+                    format!(
+                        "expected a value after '{}', found {}",
+                        op.symbol(),
+                        token.describe()
+                    ),
+                ))
+            }
+        };
+        let error = |message: String| QueryError::new(value_column, message);
+
+        let value = match field {
+            Field::Level => Value::Level(text.parse().map_err(error)?),
+            Field::Pid => Value::Pid(
+                text.parse()
+                    .map_err(|_| error(format!("invalid pid '{}'", text)))?,
+            ),
+            Field::Time => Value::Time(parse_time(&text, self.now).map_err(error)?),
+            // Сообщение regex::Error многострочное; нужна только суть в последней строке
+            _ if op.is_regex() => Value::Regex(Regex::new(&text).map_err(|err| {
+                let err = err.to_string();
+                let reason = err.lines().last().unwrap_or_default().trim();
+                error(format!(
+                    "invalid regex: {}",
+                    reason.trim_start_matches("error: ")
+                ))
+            })?),
+            _ => Value::Text(text),
+        };
+
+        Ok(Condition { field, op, value })
+    }
+}
+
+// Запрос по полям записи, например
+// `level>=warning and program=dbus-daemon and msg~"activat"`.
+// Условия объединяются `and`, `or`, `not` и скобками; строковые поля
+// сравниваются через `=`/`!=` целиком или через `~`/`!~` регулярным
+// выражением, level, pid и time — через `=`, `!=`, `<`, `<=`, `>`, `>=`.
+#[derive(Debug, Clone)]
+pub struct Query {
+    expr: Expr,
+}
+
+impl Query {
+    // Относительное время (`time>"2h ago"`) отсчитывается от `now`
+    pub fn parse(text: &str, now: DateTime<Local>) -> Result<Query, QueryError> {
+        let mut parser = QueryParser {
+            tokens: tokenize(text)?,
+            pos: 0,
+            now,
+        };
+        let expr = parser.or()?;
+        match parser.next() {
+            (_, Token::End) => Ok(Query { expr }),
+            (column, Token::Close) => Err(QueryError::new(column, "unexpected ')'")),
+            (column, token) => Err(QueryError::new(
+                column,
+                format!("expected 'and' or 'or', found {}", token.describe()),
+            )),
+        }
+    }
+
+    pub fn matches(&self, entry: &LogEntry) -> bool {
+        self.expr.matches(entry)
+    }
+}
+
+impl FromStr for Query {
+    type Err = QueryError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Query::parse(s, Local::now())
+    }
+}
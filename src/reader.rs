@@ -0,0 +1,197 @@
+use crate::archive::{self, ArchiveReader};
+use crate::journal::{is_export, ExportReader};
+use std::cmp::Ordering;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read};
+use std::path::{Path, PathBuf};
+
+// Сжатие определяется по сигнатуре в начале файла, а не по расширению
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    None,
+    Gzip,
+    Xz,
+    Bzip2,
+    Zstd,
+}
+
+// Самая длинная сигнатура — у xz
+const MAGIC_LEN: usize = 6;
+
+// Расширения сжатых файлов; нужны только для группировки ротаций
+const COMPRESSED_EXTENSIONS: [&str; 4] = ["gz", "xz", "bz2", "zst"];
+
+impl Compression {
+    pub fn from_magic(head: &[u8]) -> Compression {
+        if head.starts_with(&[0x1f, 0x8b]) {
+            Compression::Gzip
+        } else if head.starts_with(&[0xfd, b'7', b'z', b'X', b'Z', 0x00]) {
+            Compression::Xz
+        } else if head.starts_with(b"BZh") {
+            Compression::Bzip2
+        } else if head.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+            Compression::Zstd
+        } else {
+            Compression::None
+        }
+    }
+
+    pub fn detect(path: &Path) -> io::Result<Compression> {
+        let mut head = Vec::with_capacity(MAGIC_LEN);
+        File::open(path)?
+            .take(MAGIC_LEN as u64)
+            .read_to_end(&mut head)?;
+        Ok(Compression::from_magic(&head))
+    }
+}
+
+// Открывает файл логов; сжатые файлы распаковываются потоково
+pub fn open_log(path: &Path) -> io::Result<Box<dyn BufRead + Send>> {
+    open_reader(File::open(path)?)
+}
+
+// Строки потока без `\n` и `\r\n`. Байты не в UTF-8 заменяются на U+FFFD:
+// одна испорченная строка не должна обрывать чтение файла. Ошибка
+// ввода-вывода (обрезанный архив) отдаётся последним элементом.
+pub fn lossy_lines<R: BufRead>(mut reader: R) -> impl Iterator<Item = io::Result<String>> {
+    let mut buf = Vec::new();
+    let mut failed = false;
+    std::iter::from_fn(move || {
+        if failed {
+            return None;
+        }
+        buf.clear();
+        match reader.read_until(b'\n', &mut buf) {
+            Ok(0) => None,
+            Ok(_) => {
+                if buf.last() == Some(&b'\n') {
+                    buf.pop();
+                    if buf.last() == Some(&b'\r') {
+                        buf.pop();
+                    }
+                }
+                Some(Ok(String::from_utf8_lossy(&buf).into_owned()))
+            }
+            Err(err) => {
+                failed = true;
+                Some(Err(err))
+            }
+        }
+    })
+}
+
+// То же для любого потока (stdin, FIFO): сжатие определяется по первым байтам
+pub fn open_reader<R: Read + Send + 'static>(inner: R) -> io::Result<Box<dyn BufRead + Send>> {
+    let mut reader = BufReader::new(inner);
+    let compression = Compression::from_magic(reader.fill_buf()?);
+
+    let mut reader: Box<dyn BufRead + Send> = match compression {
+        Compression::None => Box::new(reader),
+        // Multi-декодеры читают и склеенные архивы (`cat a.gz b.gz`)
+        Compression::Gzip => Box::new(BufReader::new(flate2::bufread::MultiGzDecoder::new(reader))),
+        Compression::Xz => Box::new(BufReader::new(xz2::bufread::XzDecoder::new_multi_decoder(
+            reader,
+        ))),
+        Compression::Bzip2 => Box::new(BufReader::new(bzip2::bufread::MultiBzDecoder::new(reader))),
+        Compression::Zstd => Box::new(BufReader::new(zstd::Decoder::with_buffer(reader)?)),
+    };
+
+    // `journalctl -o export` не делится на строки; он перекодируется в JSON-строки
+    if is_export(reader.fill_buf()?) {
+        reader = Box::new(ExportReader::new(reader));
+    }
+    // Массив JSON, сохранённый alog, читается по записи на строку
+    if archive::is_array(reader.fill_buf()?) {
+        reader = Box::new(ArchiveReader::new(reader));
+    }
+    Ok(reader)
+}
+
+// Сколько байт (после распаковки) проверяется при поиске двоичных данных
+const BINARY_SNIFF_LEN: usize = 8192;
+
+// Файл считается двоичным, если в начале есть нулевой байт
+pub fn looks_binary(path: &Path) -> io::Result<bool> {
+    let mut head = Vec::with_capacity(BINARY_SNIFF_LEN);
+    open_log(path)?
+        .take(BINARY_SNIFF_LEN as u64)
+        .read_to_end(&mut head)?;
+    Ok(head.contains(&0))
+}
+
+// Имя файла без расширения сжатия и номер/дата ротации:
+// `daemon.log.2.gz` -> ("daemon.log", Rotation::Index(2))
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Rotation {
+    // Суффикс даты (`-20240321`): чем раньше дата, тем старше файл
+    Date(String),
+    // Числовой суффикс (`.1`, `.2.gz`): чем больше номер, тем старше файл
+    Index(u64),
+    // Текущий файл, в который ещё пишут
+    Current,
+}
+
+impl Rotation {
+    fn rank(&self) -> u8 {
+        match self {
+            Rotation::Date(_) => 0,
+            Rotation::Index(_) => 1,
+            Rotation::Current => 2,
+        }
+    }
+}
+
+impl Ord for Rotation {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (Rotation::Date(a), Rotation::Date(b)) => a.cmp(b),
+            (Rotation::Index(a), Rotation::Index(b)) => b.cmp(a),
+            _ => self.rank().cmp(&other.rank()),
+        }
+    }
+}
+
+impl PartialOrd for Rotation {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+fn rotation_key(path: &Path) -> (String, Rotation) {
+    let mut name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+
+    if let Some((stem, extension)) = name.rsplit_once('.') {
+        if COMPRESSED_EXTENSIONS.contains(&extension) {
+            name = stem.to_string();
+        }
+    }
+
+    if let Some((stem, suffix)) = name.rsplit_once('.') {
+        if !suffix.is_empty() && suffix.bytes().all(|b| b.is_ascii_digit()) {
+            if let Ok(index) = suffix.parse() {
+                return (stem.to_string(), Rotation::Index(index));
+            }
+        }
+    }
+
+    if let Some((stem, suffix)) = name.rsplit_once('-') {
+        if suffix.len() == 8 && suffix.bytes().all(|b| b.is_ascii_digit()) {
+            return (stem.to_string(), Rotation::Date(suffix.to_string()));
+        }
+    }
+
+    (name, Rotation::Current)
+}
+
+// Упорядочивает файлы так, чтобы каждая ротация читалась от самой старой
+// части к текущему файлу: daemon.log.2.gz, daemon.log.1, daemon.log
+pub fn rotation_order(mut paths: Vec<PathBuf>) -> Vec<PathBuf> {
+    paths.sort_by_cached_key(|path| {
+        let (base, rotation) = rotation_key(path);
+        (path.parent().map(Path::to_path_buf), base, rotation)
+    });
+    paths
+}
@@ -0,0 +1,474 @@
+use crate::analyzer::Consumer;
+use crate::formatter::{LogEntry, LogFormatter};
+use crate::scanner::LogStats;
+use chrono::{DateTime, Local, NaiveDateTime};
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write as _;
+use std::io::{self, Write};
+
+// Сколько предупреждений и ошибок попадает в таблицу; остальные только считаются
+pub const MAX_TABLE_ROWS: usize = 5000;
+
+// Сколько разных шаблонов сообщений запоминается; новые сверх этого идут в «прочие»
+const MAX_PATTERNS: usize = 50_000;
+
+// Строк в сводках по файлам и программам и в списке частых сообщений
+const TOP_ROWS: usize = 25;
+
+// Наибольшее число столбцов гистограммы
+const HISTOGRAM_BARS: i64 = 60;
+
+// Ширина столбца гистограммы в минутах: от минуты до месяца
+const HISTOGRAM_STEPS: [i64; 15] = [
+    1, 2, 5, 10, 15, 30, 60, 120, 180, 360, 720, 1440, 2880, 10080, 43200,
+];
+
+// Одинаковые сообщения, которые различаются только числами (pid, адреса, счётчики)
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pattern {
+    pub text: String,
+    pub count: usize,
+    // Самый важный уровень среди совпавших записей
+    pub level: LogFormatter,
+    pub program: Option<String>,
+}
+
+// Столбцы гистограммы ошибок и предупреждений
+#[derive(Debug, Clone, PartialEq)]
+pub struct Histogram {
+    pub start: NaiveDateTime,
+    pub step_minutes: i64,
+    // (ошибки и важнее, предупреждения) в каждом столбце
+    pub bars: Vec<(usize, usize)>,
+}
+
+// Отчёт для рассылки после инцидента (`alog report --html`): сводка по
+// уровням, файлам и программам, гистограмма ошибок, частые сообщения и
+// таблица предупреждений и ошибок. Собирается из тех же записей, что и
+// остальные получатели, и хранит только счётчики и ограниченную таблицу.
+#[derive(Default)]
+pub struct Report {
+    pub stats: LogStats,
+    files: HashMap<String, usize>,
+    programs: HashMap<String, usize>,
+    // Минута местного времени -> (ошибки и важнее, предупреждения)
+    timeline: BTreeMap<i64, (usize, usize)>,
+    // Предупреждения и ошибки без времени, в гистограмму не попавшие
+    pub undated: usize,
+    patterns: HashMap<String, Pattern>,
+    pub other_patterns: usize,
+    rows: Vec<LogEntry>,
+    pub dropped_rows: usize,
+}
+
+// Числа заменяются на `#`, чтобы `pid 612` и `pid 613` считались одним сообщением
+fn normalize(text: &str) -> String {
+    let mut pattern = String::with_capacity(text.len());
+    let mut in_number = false;
+    for ch in text.chars() {
+        if ch.is_ascii_digit() {
+            if !in_number {
+                pattern.push('#');
+            }
+            in_number = true;
+        } else {
+            pattern.push(ch);
+            in_number = false;
+        }
+    }
+    pattern
+}
+
+fn is_error(level: LogFormatter) -> bool {
+    level >= LogFormatter::Error
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for ch in text.chars() {
+        match ch {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(ch),
+        }
+    }
+    escaped
+}
+
+fn minute_time(minute: i64) -> NaiveDateTime {
+    DateTime::from_timestamp(minute * 60, 0)
+        .unwrap_or_default()
+        .naive_utc()
+}
+
+fn top(counts: &HashMap<String, usize>) -> Vec<(&str, usize)> {
+    let mut top: Vec<(&str, usize)> = counts
+        .iter()
+        .map(|(name, count)| (name.as_str(), *count))
+        .collect();
+    top.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+    top
+}
+
+impl Report {
+    pub fn new() -> Report {
+        Report::default()
+    }
+
+    // Сообщения по убыванию числа повторов
+    pub fn top_messages(&self, limit: usize) -> Vec<&Pattern> {
+        let mut patterns: Vec<&Pattern> = self.patterns.values().collect();
+        patterns.sort_by(|a, b| b.count.cmp(&a.count).then(a.text.cmp(&b.text)));
+        patterns.truncate(limit);
+        patterns
+    }
+
+    // Ширина столбца подбирается так, чтобы столбцов было не больше HISTOGRAM_BARS
+    pub fn histogram(&self) -> Option<Histogram> {
+        let first = *self.timeline.keys().next()?;
+        let last = *self.timeline.keys().next_back()?;
+        // Столбцы выровнены по ширине, поэтому первый может начинаться раньше first
+        let bars = |step: i64| (last - (first - first.rem_euclid(step))) / step + 1;
+        let step = HISTOGRAM_STEPS
+            .into_iter()
+            .find(|step| bars(*step) <= HISTOGRAM_BARS)
+            .unwrap_or_else(|| (last - first) / (HISTOGRAM_BARS - 1) + 1);
+        let start = first - first.rem_euclid(step);
+
+        let mut bars = vec![(0, 0); ((last - start) / step + 1) as usize];
+        for (minute, (errors, warnings)) in &self.timeline {
+            let bar = &mut bars[((minute - start) / step) as usize];
+            bar.0 += errors;
+            bar.1 += warnings;
+        }
+        Some(Histogram {
+            start: minute_time(start),
+            step_minutes: step,
+            bars,
+        })
+    }
+
+    pub fn rows(&self) -> &[LogEntry] {
+        &self.rows
+    }
+
+    // Один HTML-файл без внешних ресурсов: стили, SVG и поиск встроены
+    pub fn write_html<W: Write>(
+        &self,
+        mut out: W,
+        sources: &[String],
+        generated: DateTime<Local>,
+    ) -> io::Result<()> {
+        let mut html = String::new();
+        self.render(&mut html, sources, generated)
+            .map_err(|_| io::Error::other("failed to render report"))?;
+        out.write_all(html.as_bytes())?;
+        out.flush()
+    }
+
+    fn render(
+        &self,
+        html: &mut String,
+        sources: &[String],
+        generated: DateTime<Local>,
+    ) -> std::fmt::Result {
+        writeln!(
+            html,
+            "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n\
+             <title>alog report</title>\n<style>{}</style>\n</head>\n<body>",
+            STYLE
+        )?;
+        writeln!(html, "<h1>Log report</h1>")?;
+        writeln!(
+            html,
+            "<p class=\"meta\">Generated {} from {}</p>",
+            generated.format("%Y-%m-%d %H:%M:%S %:z"),
+            escape(&sources.join(", "))
+        )?;
+
+        writeln!(html, "<h2>Levels</h2>\n<table class=\"counts\">")?;
+        writeln!(
+            html,
+            "<tr><th>Total</th><td>{}</td><td></td></tr>",
+            self.stats.total_messages
+        )?;
+        for level in LogFormatter::ALL {
+            let count = self.stats.count(level);
+            let share = count * 100 / self.stats.total_messages.max(1);
+            writeln!(
+                html,
+                "<tr class=\"{}\"><th>{}</th><td>{}</td>\
+                 <td><div class=\"bar\" style=\"width:{}%\"></div></td></tr>",
+                level.title().to_lowercase(),
+                level.title(),
+                count,
+                share
+            )?;
+        }
+        writeln!(html, "</table>")?;
+
+        writeln!(html, "<div class=\"columns\">")?;
+        for (title, counts) in [("Files", &self.files), ("Programs", &self.programs)] {
+            writeln!(
+                html,
+                "<section><h2>{}</h2>\n<table class=\"counts\">",
+                title
+            )?;
+            let top = top(counts);
+            for (name, count) in top.iter().take(TOP_ROWS) {
+                writeln!(html, "<tr><th>{}</th><td>{}</td></tr>", escape(name), count)?;
+            }
+            if top.len() > TOP_ROWS {
+                let rest: usize = top[TOP_ROWS..].iter().map(|(_, count)| count).sum();
+                writeln!(
+                    html,
+                    "<tr><th class=\"muted\">{} more</th><td>{}</td></tr>",
+                    top.len() - TOP_ROWS,
+                    rest
+                )?;
+            }
+            writeln!(html, "</table></section>")?;
+        }
+        writeln!(html, "</div>")?;
+
+        writeln!(html, "<h2>Errors and warnings over time</h2>")?;
+        match self.histogram() {
+            Some(histogram) => render_histogram(html, &histogram)?,
+            None => writeln!(html, "<p class=\"muted\">No dated errors or warnings.</p>")?,
+        }
+        if self.undated > 0 {
+            writeln!(
+                html,
+                "<p class=\"muted\">{} errors and warnings without a timestamp are not shown.</p>",
+                self.undated
+            )?;
+        }
+
+        writeln!(
+            html,
+            "<h2>Top messages</h2>\n<table class=\"list\">\n\
+             <tr><th>Count</th><th>Level</th><th>Program</th><th>Message</th></tr>"
+        )?;
+        for pattern in self.top_messages(TOP_ROWS) {
+            writeln!(
+                html,
+                "<tr class=\"{}\"><td>{}</td><td>{}</td><td>{}</td><td class=\"msg\">{}</td></tr>",
+                pattern.level.title().to_lowercase(),
+                pattern.count,
+                pattern.level.title(),
+                escape(pattern.program.as_deref().unwrap_or_default()),
+                escape(&pattern.text)
+            )?;
+        }
+        writeln!(html, "</table>")?;
+        if self.other_patterns > 0 {
+            writeln!(
+                html,
+                "<p class=\"muted\">{} messages were not grouped: too many distinct messages.</p>",
+                self.other_patterns
+            )?;
+        }
+
+        writeln!(
+            html,
+            "<h2>Errors and warnings</h2>\n\
+             <p><input id=\"search\" type=\"search\" placeholder=\"Search\" autocomplete=\"off\"> \
+             <span id=\"shown\">{count} of {count}</span></p>\n\
+             <table class=\"list\" id=\"entries\">\n\
+             <tr><th>Time</th><th>Level</th><th>File</th><th>Program</th><th>Message</th></tr>",
+            count = self.rows.len()
+        )?;
+        for entry in &self.rows {
+            let time = entry
+                .timestamp()
+                .map(|time| time.format("%Y-%m-%d %H:%M:%S").to_string())
+                .unwrap_or_default();
+            writeln!(
+                html,
+                "<tr class=\"{}\"><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td class=\"msg\">{}</td></tr>",
+                entry.level.title().to_lowercase(),
+                time,
+                entry.level.title(),
+                escape(&entry.file_path.to_string_lossy()),
+                escape(entry.program.as_deref().unwrap_or_default()),
+                escape(entry.body.as_deref().unwrap_or(&entry.message))
+            )?;
+        }
+        writeln!(html, "</table>")?;
+        if self.dropped_rows > 0 {
+            writeln!(
+                html,
+                "<p class=\"muted\">Only the first {} entries are listed; {} more are counted above.</p>",
+                MAX_TABLE_ROWS, self.dropped_rows
+            )?;
+        }
+
+        writeln!(html, "<script>{}</script>\n</body>\n</html>", SCRIPT)
+    }
+}
+
+fn render_histogram(html: &mut String, histogram: &Histogram) -> std::fmt::Result {
+    const BAR_WIDTH: usize = 12;
+    const HEIGHT: usize = 120;
+    let highest = histogram
+        .bars
+        .iter()
+        .map(|(errors, warnings)| errors + warnings)
+        .max()
+        .unwrap_or(0)
+        .max(1);
+    let width = histogram.bars.len() * BAR_WIDTH;
+
+    writeln!(
+        html,
+        "<svg class=\"histogram\" viewBox=\"0 0 {} {}\" preserveAspectRatio=\"none\">",
+        width, HEIGHT
+    )?;
+    for (index, (errors, warnings)) in histogram.bars.iter().enumerate() {
+        let from =
+            histogram.start + chrono::Duration::minutes(histogram.step_minutes * index as i64);
+        let x = index * BAR_WIDTH;
+        let error_height = errors * HEIGHT / highest;
+        let warning_height = warnings * HEIGHT / highest;
+        writeln!(
+            html,
+            "<g><title>{}: {} errors, {} warnings</title>\
+             <rect class=\"warning\" x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\"/>\
+             <rect class=\"error\" x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\"/></g>",
+            from.format("%Y-%m-%d %H:%M"),
+            errors,
+            warnings,
+            x,
+            HEIGHT - error_height - warning_height,
+            BAR_WIDTH - 2,
+            warning_height,
+            x,
+            HEIGHT - error_height,
+            BAR_WIDTH - 2,
+            error_height
+        )?;
+    }
+    writeln!(html, "</svg>")?;
+
+    let end = histogram.start
+        + chrono::Duration::minutes(histogram.step_minutes * histogram.bars.len() as i64);
+    writeln!(
+        html,
+        "<p class=\"axis\"><span>{}</span><span>{} per bar, highest {}</span><span>{}</span></p>",
+        histogram.start.format("%Y-%m-%d %H:%M"),
+        step_title(histogram.step_minutes),
+        highest,
+        end.format("%Y-%m-%d %H:%M")
+    )
+}
+
+fn step_title(minutes: i64) -> String {
+    match minutes {
+        minutes if minutes % 1440 == 0 => format!("{} d", minutes / 1440),
+        minutes if minutes % 60 == 0 => format!("{} h", minutes / 60),
+        minutes => format!("{} min", minutes),
+    }
+}
+
+impl Consumer for Report {
+    fn consume(&mut self, entry: &LogEntry) -> io::Result<()> {
+        self.stats.record(entry.level);
+        *self
+            .files
+            .entry(entry.file_path.to_string_lossy().into_owned())
+            .or_default() += 1;
+        if let Some(program) = &entry.program {
+            *self.programs.entry(program.clone()).or_default() += 1;
+        }
+
+        // Для частых сообщений берётся первая строка без заголовка
+        let text = entry.body.as_deref().unwrap_or(&entry.message);
+        let key = normalize(text.lines().next().unwrap_or_default());
+        let patterns = self.patterns.len();
+        match self.patterns.entry(key) {
+            Entry::Occupied(mut pattern) => {
+                let pattern = pattern.get_mut();
+                pattern.count += 1;
+                pattern.level = pattern.level.max(entry.level);
+            }
+            Entry::Vacant(slot) if patterns < MAX_PATTERNS => {
+                let text = slot.key().clone();
+                slot.insert(Pattern {
+                    text,
+                    count: 1,
+                    level: entry.level,
+                    program: entry.program.clone(),
+                });
+            }
+            Entry::Vacant(_) => self.other_patterns += 1,
+        }
+
+        if entry.level < LogFormatter::Warning {
+            return Ok(());
+        }
+        match entry.timestamp() {
+            Some(timestamp) => {
+                let local = timestamp.with_timezone(&Local).naive_local();
+                let bucket = self
+                    .timeline
+                    .entry(local.and_utc().timestamp().div_euclid(60))
+                    .or_default();
+                if is_error(entry.level) {
+                    bucket.0 += 1;
+                } else {
+                    bucket.1 += 1;
+                }
+            }
+            None => self.undated += 1,
+        }
+        if self.rows.len() < MAX_TABLE_ROWS {
+            self.rows.push(entry.clone());
+        } else {
+            self.dropped_rows += 1;
+        }
+        Ok(())
+    }
+}
+
+const STYLE: &str = "
+body { font-family: sans-serif; margin: 2em; color: #222; }
+h1 { margin-bottom: 0; }
+.meta, .muted { color: #777; }
+table { border-collapse: collapse; margin-bottom: 1em; }
+th, td { padding: 2px 8px; text-align: left; vertical-align: top; border-bottom: 1px solid #eee; }
+.counts td { text-align: right; min-width: 4em; }
+.counts td:last-child { width: 20em; }
+.bar { height: 0.8em; background: #8ab; }
+.columns { display: flex; gap: 3em; flex-wrap: wrap; }
+.list { width: 100%; font-size: 90%; }
+.msg { font-family: monospace; white-space: pre-wrap; word-break: break-all; }
+tr.emergency, tr.alert, tr.critical, tr.error { background: #fde8e8; }
+tr.warning { background: #fff5db; }
+.histogram { width: 100%; height: 160px; background: #fafafa; }
+.histogram rect.error { fill: #d33; }
+.histogram rect.warning { fill: #f0a020; }
+.axis { display: flex; justify-content: space-between; color: #777; font-size: 85%; margin-top: 0; }
+#search { width: 30em; padding: 4px; }
+";
+
+// Строка остаётся, если содержит все слова запроса
+const SCRIPT: &str = "
+const search = document.getElementById('search');
+const rows = Array.from(document.querySelectorAll('#entries tr')).slice(1);
+const shown = document.getElementById('shown');
+search.addEventListener('input', () => {
+  const words = search.value.toLowerCase().split(/\\s+/).filter(Boolean);
+  let count = 0;
+  for (const row of rows) {
+    const text = row.textContent.toLowerCase();
+    const visible = words.every(word => text.includes(word));
+    row.style.display = visible ? '' : 'none';
+    if (visible) count++;
+  }
+  shown.textContent = count + ' of ' + rows.length;
+});
+";
@@ -0,0 +1,128 @@
+use crate::formatter::LogFormatter;
+use eyre::{eyre, WrapErr};
+use regex::{Regex, RegexSet};
+use serde::Deserialize;
+use std::path::Path;
+
+// Правило в файле, например:
+//
+// [[rule]]
+// name = "dbus activation"
+// regex = 'dbus-daemon\[\d+\]: .*Activating'
+// level = "notice"
+// tags = ["dbus"]
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+struct RuleConfig {
+    name: Option<String>,
+    regex: Option<String>,
+    contains: Option<String>,
+    level: String,
+    #[serde(default)]
+    tags: Vec<String>,
+    #[serde(default)]
+    ignore_case: bool,
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(deny_unknown_fields)]
+struct RulesFile {
+    #[serde(default)]
+    rule: Vec<RuleConfig>,
+}
+
+#[derive(Debug)]
+pub struct Rule {
+    pub name: String,
+    // Подстроки из `contains` тоже компилируются в регулярное выражение
+    pub pattern: Regex,
+    pub level: LogFormatter,
+    pub tags: Vec<String>,
+}
+
+// Упорядоченный список пользовательских правил: срабатывает первое подходящее
+// Все выражения также собраны в RegexSet, чтобы проверять строку за один проход
+#[derive(Debug)]
+pub struct RuleSet {
+    pub rules: Vec<Rule>,
+    set: RegexSet,
+}
+
+impl Default for RuleSet {
+    fn default() -> Self {
+        RuleSet {
+            rules: Vec::new(),
+            set: RegexSet::empty(),
+        }
+    }
+}
+
+impl RuleSet {
+    pub fn load(path: &Path) -> eyre::Result<RuleSet> {
+        let content = std::fs::read_to_string(path)
+            .wrap_err_with(|| format!("Failed to read rules file {}", path.display()))?;
+        RuleSet::from_toml(&content)
+            .wrap_err_with(|| format!("Invalid rules file {}", path.display()))
+    }
+
+    pub fn from_toml(content: &str) -> eyre::Result<RuleSet> {
+        let file: RulesFile = toml::from_str(content)?;
+        let mut rules = Vec::with_capacity(file.rule.len());
+
+        for (index, config) in file.rule.into_iter().enumerate() {
+            let name = config
+                .name
+                .unwrap_or_else(|| format!("rule #{}", index + 1));
+
+            let source = match (config.regex, config.contains) {
+                (Some(pattern), None) => pattern,
+                (None, Some(needle)) => regex::escape(&needle),
+                _ => {
+                    return Err(eyre!(
+                        "{} must set exactly one of `regex` or `contains`",
+                        name
+                    ))
+                }
+            };
+            // Флаг встраивается в само выражение, чтобы RegexSet его тоже учитывал
+            let source = if config.ignore_case {
+                format!("(?i:{})", source)
+            } else {
+                source
+            };
+            let pattern =
+                Regex::new(&source).wrap_err_with(|| format!("Invalid regex in {}", name))?;
+
+            let level = config
+                .level
+                .parse()
+                .map_err(|err: String| eyre!("{}: {}", name, err))?;
+
+            rules.push(Rule {
+                name,
+                pattern,
+                level,
+                tags: config.tags,
+            });
+        }
+
+        let set = RegexSet::new(rules.iter().map(|rule| rule.pattern.as_str()))?;
+        Ok(RuleSet { rules, set })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    // Номер первого сработавшего правила
+    pub fn find_index(&self, line: &str) -> Option<usize> {
+        if self.rules.is_empty() {
+            return None;
+        }
+        self.set.matches(line).iter().next()
+    }
+
+    pub fn find(&self, line: &str) -> Option<&Rule> {
+        self.find_index(line).map(|index| &self.rules[index])
+    }
+}
@@ -1,49 +1,96 @@
-use regex::Regex;
+use crate::analyzer::Analyzer;
+use crate::classifier::Classifier;
+use crate::formatter::LogFormatter;
+use crate::parser::PlainParser;
+use std::path::PathBuf;
 
 #[derive(Clone)]
 pub struct LogStats {
     pub total_messages: usize,
-    pub info_messages: usize,
-    pub warning_messages: usize,
+    pub emergency_messages: usize,
+    pub alert_messages: usize,
+    pub critical_messages: usize,
     pub error_messages: usize,
+    pub warning_messages: usize,
+    pub notice_messages: usize,
+    pub info_messages: usize,
+    pub debug_messages: usize,
     pub trace_messages: usize,
 }
 
+impl Default for LogStats {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl LogStats {
     pub fn new() -> LogStats {
         LogStats {
             total_messages: 0,
-            info_messages: 0,
-            warning_messages: 0,
+            emergency_messages: 0,
+            alert_messages: 0,
+            critical_messages: 0,
             error_messages: 0,
+            warning_messages: 0,
+            notice_messages: 0,
+            info_messages: 0,
+            debug_messages: 0,
             trace_messages: 0,
         }
     }
 
     pub async fn analyze_log_line(&mut self, line: &str) {
+        let classifier = Classifier::default();
+        let entry = Analyzer::new(&classifier).entry(&PlainParser, line, PathBuf::new());
+        self.record(entry.level);
+    }
+
+    pub fn record(&mut self, level: LogFormatter) {
         self.total_messages += 1;
+        *self.count_mut(level) += 1;
+    }
+
+    pub fn count(&self, level: LogFormatter) -> usize {
+        match level {
+            LogFormatter::Emergency => self.emergency_messages,
+            LogFormatter::Alert => self.alert_messages,
+            LogFormatter::Critical => self.critical_messages,
+            LogFormatter::Error => self.error_messages,
+            LogFormatter::Warning => self.warning_messages,
+            LogFormatter::Notice => self.notice_messages,
+            LogFormatter::Info => self.info_messages,
+            LogFormatter::Debug => self.debug_messages,
+            LogFormatter::Trace => self.trace_messages,
+        }
+    }
 
-        let info_regex = Regex::new(r"(?i)info").unwrap();
-        let warning_regex = Regex::new(r"(?i)warning").unwrap();
-        let error_regex = Regex::new(r"(?i)error").unwrap();
-        let trace_regex = Regex::new(r"(?i)").unwrap();
-
-        if info_regex.is_match(line) {
-            self.info_messages += 1;
-        } else if warning_regex.is_match(line) {
-            self.warning_messages += 1;
-        } else if error_regex.is_match(line) {
-            self.error_messages += 1;
-        } else if trace_regex.is_match(line) {
-            self.trace_messages += 1;
+    fn count_mut(&mut self, level: LogFormatter) -> &mut usize {
+        match level {
+            LogFormatter::Emergency => &mut self.emergency_messages,
+            LogFormatter::Alert => &mut self.alert_messages,
+            LogFormatter::Critical => &mut self.critical_messages,
+            LogFormatter::Error => &mut self.error_messages,
+            LogFormatter::Warning => &mut self.warning_messages,
+            LogFormatter::Notice => &mut self.notice_messages,
+            LogFormatter::Info => &mut self.info_messages,
+            LogFormatter::Debug => &mut self.debug_messages,
+            LogFormatter::Trace => &mut self.trace_messages,
         }
     }
 
+    // Строки для вывода: общее число и счётчики по уровням
+    pub fn summary(&self) -> Vec<String> {
+        let mut lines = vec![format!("Total messages: {}", self.total_messages)];
+        for level in LogFormatter::ALL {
+            lines.push(format!("{} messages: {}", level.title(), self.count(level)));
+        }
+        lines
+    }
+
     pub fn print_stats(&self) {
-        println!("Total messages: {}", self.total_messages);
-        println!("Info messages: {}", self.info_messages);
-        println!("Warning messages: {}", self.warning_messages);
-        println!("Error messages: {}", self.error_messages);
-        println!("Trace messages: {}", self.trace_messages);
+        for line in self.summary() {
+            println!("{}", line);
+        }
     }
 }
@@ -0,0 +1,154 @@
+use crate::formatter::LogEntry;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, BufWriter, Lines, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+// Записи источника, разобранные до слияния: первые — в памяти, пока не
+// исчерпан общий для всех очередей запас, остальные — во временном файле
+// NDJSON. Файл открыт только пока в него пишут и пока его читают, поэтому
+// число открытых файлов не зависит от числа источников.
+#[derive(Default)]
+pub struct Spool {
+    entries: Vec<LogEntry>,
+    file: Option<SpoolFile>,
+}
+
+// Временный файл, удаляется вместе с владельцем
+struct SpoolFile(PathBuf);
+
+impl SpoolFile {
+    fn create() -> io::Result<(SpoolFile, File)> {
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+        let path = std::env::temp_dir().join(format!(
+            "alog-spool-{}-{}",
+            std::process::id(),
+            NEXT.fetch_add(1, Ordering::Relaxed)
+        ));
+        let file = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&path)?;
+        Ok((SpoolFile(path), file))
+    }
+}
+
+impl Drop for SpoolFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
+pub struct SpoolWriter<'a> {
+    // Сколько записей ещё можно держать в памяти
+    memory: &'a AtomicUsize,
+    entries: Vec<LogEntry>,
+    file: Option<(SpoolFile, BufWriter<File>)>,
+}
+
+impl<'a> SpoolWriter<'a> {
+    pub fn new(memory: &'a AtomicUsize) -> Self {
+        SpoolWriter {
+            memory,
+            entries: Vec::new(),
+            file: None,
+        }
+    }
+
+    pub fn push(&mut self, entry: LogEntry) -> io::Result<()> {
+        // После первой записи в файл в него идут все остальные, чтобы не нарушить порядок
+        if self.file.is_none()
+            && self
+                .memory
+                .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |left| {
+                    left.checked_sub(1)
+                })
+                .is_ok()
+        {
+            self.entries.push(entry);
+            return Ok(());
+        }
+        let (_, out) = match &mut self.file {
+            Some(file) => file,
+            None => {
+                let (spool, file) = SpoolFile::create()?;
+                self.file.insert((spool, BufWriter::new(file)))
+            }
+        };
+        serde_json::to_writer(&mut *out, &entry)?;
+        out.write_all(b"\n")
+    }
+
+    pub fn finish(self) -> io::Result<Spool> {
+        let file = match self.file {
+            Some((spool, mut out)) => {
+                out.flush()?;
+                Some(spool)
+            }
+            None => None,
+        };
+        Ok(Spool {
+            entries: self.entries,
+            file,
+        })
+    }
+}
+
+impl Spool {
+    // Записи по порядку. Ошибка чтения файла завершает поток и сохраняется в
+    // `failed` (первая из всех)
+    pub fn reader(self, failed: &Mutex<Option<io::Error>>) -> SpoolReader<'_> {
+        SpoolReader {
+            entries: self.entries.into_iter(),
+            file: self.file,
+            lines: None,
+            failed,
+        }
+    }
+}
+
+pub struct SpoolReader<'a> {
+    entries: std::vec::IntoIter<LogEntry>,
+    file: Option<SpoolFile>,
+    // Файл открывается, когда закончатся записи в памяти
+    lines: Option<Lines<BufReader<File>>>,
+    failed: &'a Mutex<Option<io::Error>>,
+}
+
+impl SpoolReader<'_> {
+    fn read(&mut self) -> io::Result<Option<LogEntry>> {
+        let Some(spool) = &self.file else {
+            return Ok(None);
+        };
+        if self.lines.is_none() {
+            self.lines = Some(BufReader::new(File::open(&spool.0)?).lines());
+        }
+        match self.lines.as_mut().and_then(Iterator::next) {
+            Some(line) => Ok(Some(serde_json::from_str(&line?)?)),
+            None => Ok(None),
+        }
+    }
+}
+
+impl Iterator for SpoolReader<'_> {
+    type Item = LogEntry;
+
+    fn next(&mut self) -> Option<LogEntry> {
+        if let Some(entry) = self.entries.next() {
+            return Some(entry);
+        }
+        match self.read() {
+            Ok(Some(entry)) => Some(entry),
+            result => {
+                // Прочитанный файл сразу закрывается и удаляется
+                self.lines = None;
+                self.file = None;
+                if let Err(err) = result {
+                    self.failed.lock().unwrap().get_or_insert(err);
+                }
+                None
+            }
+        }
+    }
+}
@@ -2,11 +2,56 @@
 #[cfg(test)]
 mod tests {
 
-    use crate::formatter::{LogEntry, LogFormatter, Logs};
+    use crate::analyzer::{Analyzer, Consumer};
+    use crate::archive::ArchiveParser;
+    use crate::classifier::Classifier;
+    use crate::exporter::{delimiter_for, Column, CsvWriter, JsonMode, JsonWriter};
+    use crate::filter::LineFilter;
+    use crate::follow::Follower;
+    use crate::formatter::{LevelFilter, LogEntry, LogFormatter, Logs};
+    use crate::ingest::{merge_by_time, parse_sources, seek_since, Reading, Source};
+    use crate::journal::{ExportReader, JournalParser};
+    use crate::multiline::Joiner;
+    use crate::parser::{
+        detect_format, keyword_level, AppParser, LogFormat, LogParser, PlainParser, SyslogParser,
+        XorgParser,
+    };
+    use crate::query::Query;
+    use crate::reader::{open_log, open_reader, rotation_order, Compression};
+    use crate::report::Report;
+    use crate::rules::RuleSet;
     use crate::scanner::LogStats;
-    use crate::vizualizer::EllipticCurve;
-    use chrono::Local;
-    use std::path::PathBuf;
+    use crate::timerange::{parse_time, TimeRange};
+    use crate::vizualizer::{App, EllipticCurve};
+    use crate::walker::Walker;
+    use chrono::{Local, TimeZone};
+    use std::path::{Path, PathBuf};
+
+    // Временная директория теста; удаляется и тогда, когда проверка не прошла
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let path = std::env::temp_dir().join(format!("alog_{}_{}", name, std::process::id()));
+            let _ = std::fs::remove_dir_all(&path);
+            std::fs::create_dir_all(&path).unwrap();
+            TempDir(path)
+        }
+
+        fn path(&self) -> &Path {
+            &self.0
+        }
+
+        fn join(&self, name: &str) -> PathBuf {
+            self.0.join(name)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
 
     // Unit Tests
     #[cfg(test)]
@@ -19,12 +64,12 @@ mod tests {
             let log_entry = LogEntry::new(
                 LogFormatter::Info,
                 String::from("Test message"),
-                date,
+                Some(date),
                 PathBuf::from("/test/path"),
             );
 
             assert_eq!(log_entry.message, "Test message");
-            assert_eq!(log_entry.date, date.to_rfc3339());
+            assert_eq!(log_entry.date, Some(date.to_rfc3339()));
             assert_eq!(log_entry.file_path, PathBuf::from("/test/path"));
         }
 
@@ -45,12 +90,652 @@ mod tests {
             assert_eq!(stats.trace_messages, 0);
         }
 
+        #[test]
+        fn test_level_ordering_and_filter() {
+            assert!(LogFormatter::Emergency > LogFormatter::Critical);
+            assert!(LogFormatter::Error > LogFormatter::Warning);
+            assert!(LogFormatter::Debug > LogFormatter::Trace);
+            assert_eq!("crit".parse(), Ok(LogFormatter::Critical));
+            assert_eq!("fatal".parse(), Ok(LogFormatter::Critical));
+            assert_eq!(LogFormatter::from_severity(5), Some(LogFormatter::Notice));
+            assert_eq!(LogFormatter::Trace.severity(), None);
+
+            let filter: LevelFilter = "error+".parse().unwrap();
+            assert!(filter.matches(LogFormatter::Alert));
+            assert!(filter.matches(LogFormatter::Error));
+            assert!(!filter.matches(LogFormatter::Warning));
+
+            let filter: LevelFilter = "notice".parse().unwrap();
+            assert!(filter.matches(LogFormatter::Notice));
+            assert!(!filter.matches(LogFormatter::Error));
+            assert!("verbose".parse::<LevelFilter>().is_err());
+        }
+
+        #[test]
+        fn test_classification_markers_and_keywords() {
+            assert_eq!(
+                keyword_level("Failed to get information: error"),
+                LogFormatter::Error
+            );
+            assert_eq!(keyword_level("scan done, errors=0"), LogFormatter::Trace);
+            assert_eq!(
+                keyword_level("info: fatal signal received"),
+                LogFormatter::Critical
+            );
+
+            let syslog = SyslogParser::new();
+            let parsed = syslog.parse(
+                "May 13 20:23:12 astra NetworkManager[14257]: <warn>  [1715620992.7] dhcp4: request timed out, error",
+            );
+            assert_eq!(parsed.classify(), LogFormatter::Warning);
+
+            let xorg = XorgParser::new();
+            assert_eq!(
+                xorg.parse("[    14.453] (EE) Failed to load module \"fbdev\"")
+                    .classify(),
+                LogFormatter::Error
+            );
+            assert_eq!(
+                xorg.parse("[    14.203] (II) Loader magic: 0x55b1e0, info")
+                    .classify(),
+                LogFormatter::Info
+            );
+
+            let app = AppParser::new();
+            assert_eq!(
+                app.parse("04/03/22 08:51:06 TRACE  :...read_physical_netif: error count 0")
+                    .classify(),
+                LogFormatter::Trace
+            );
+        }
+
+        #[test]
+        fn test_rule_set_from_toml() {
+            let rules = RuleSet::from_toml(
+                r#"
+                [[rule]]
+                name = "dbus activation"
+                regex = 'dbus-daemon\[\d+\]: .*Activating'
+                level = "notice"
+                tags = ["dbus"]
+
+                [[rule]]
+                contains = "SEGFAULT"
+                ignore_case = true
+                level = "crit"
+                "#,
+            )
+            .unwrap();
+
+            let rule = rules
+                .find("Mar 22 11:53:18 astra dbus-daemon[383]: [system] Activating service")
+                .unwrap();
+            assert_eq!(rule.name, "dbus activation");
+            assert_eq!(rule.level, LogFormatter::Notice);
+            assert_eq!(rule.tags, vec!["dbus"]);
+
+            let rule = rules.find("app[12]: segfault at 0").unwrap();
+            assert_eq!(rule.name, "rule #2");
+            assert_eq!(rule.level, LogFormatter::Critical);
+
+            assert!(rules.find("nothing to see").is_none());
+
+            assert!(RuleSet::from_toml("[[rule]]\nlevel = \"info\"").is_err());
+            assert!(RuleSet::from_toml("[[rule]]\ncontains = \"x\"\nlevel = \"loud\"").is_err());
+        }
+
         #[test]
         fn test_elliptic_curve_calculate_points() {
             let curve = EllipticCurve::new(1.0, -1.0);
             let points = curve.calculate_points(-2.0, 2.0, 0.5);
             assert!(!points.is_empty());
         }
+
+        #[test]
+        fn test_live_app_scroll_and_pause() {
+            use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+            let key = |code| KeyEvent::new(code, KeyModifiers::NONE);
+            let error = |message: &str| {
+                LogEntry::new(
+                    LogFormatter::Error,
+                    message.to_string(),
+                    None,
+                    PathBuf::from("live.log"),
+                )
+            };
+
+            let mut app = App::new(Logs::new(), LogStats::new(), None);
+            for message in ["one", "two", "three"] {
+                app.push(error(message));
+            }
+            // Автопрокрутка держит последнюю запись выбранной
+            assert_eq!(app.selected(Some(LogFormatter::Error)), Some(2));
+
+            // Пользователь прокрутил вверх: новые записи не сдвигают выбор
+            assert!(app.handle_key(key(KeyCode::Up)));
+            app.push(error("four"));
+            assert_eq!(app.selected(Some(LogFormatter::Error)), Some(1));
+            app.handle_key(key(KeyCode::End));
+            assert_eq!(app.selected(Some(LogFormatter::Error)), Some(3));
+
+            // На паузе счётчики растут, а списки нет
+            app.handle_key(key(KeyCode::Char(' ')));
+            assert!(app.is_paused());
+            app.push(error("five"));
+            assert_eq!(app.stats().error_messages, 5);
+            assert_eq!(app.logs().entries.len(), 4);
+            app.handle_key(key(KeyCode::Char(' ')));
+            assert_eq!(app.logs().entries.len(), 5);
+            assert_eq!(app.selected(Some(LogFormatter::Error)), Some(4));
+
+            // Общая лента (--merge) прокручивается отдельно от колонок
+            app.handle_key(key(KeyCode::Char('m')));
+            assert_eq!(app.selected(None), Some(4));
+            app.handle_key(key(KeyCode::Home));
+            assert_eq!(app.selected(None), Some(0));
+            assert_eq!(app.selected(Some(LogFormatter::Error)), Some(4));
+
+            assert!(!app.handle_key(key(KeyCode::Char('q'))));
+        }
+
+        #[test]
+        fn test_query_language() {
+            let now = Local::now();
+            let mut entry = LogEntry::new(
+                LogFormatter::Warning,
+                "Mar 22 11:53:19 astra dbus-daemon[453]: Activating service".to_string(),
+                Some(Local.with_ymd_and_hms(2024, 3, 22, 11, 53, 19).unwrap()),
+                PathBuf::from("/var/log/daemon.log"),
+            );
+            entry.host = Some("astra".to_string());
+            entry.program = Some("dbus-daemon".to_string());
+            entry.pid = Some(453);
+            entry.tags = vec!["bus".to_string()];
+            let matches = |text: &str| Query::parse(text, now).unwrap().matches(&entry);
+
+            assert!(matches(
+                r#"level>=warning and program=dbus-daemon and msg~"(?i)activat""#
+            ));
+            assert!(!matches("level>warning"));
+            assert!(matches("level<=warning and level!=error"));
+            assert!(matches("pid>400 and pid<500 and host=astra"));
+            assert!(matches("not program=systemd or pid=1"));
+            assert!(!matches("program=systemd or (pid=1 and host=astra)"));
+            assert!(matches("tag=bus and file~'daemon[.]log$'"));
+            // `\` перед другими символами остаётся частью регулярного выражения
+            assert!(matches(r#"msg~"\[\d+\]: \w+" and file~'\.log$'"#));
+            assert!(matches(r#"msg~"\\[\\d+\\]""#));
+            assert!(matches(r#"msg!~'it\'s' and body!~"\"""#));
+            assert!(matches(
+                r#"time>="2024-03-22 11:00" AND time<"2024-03-22 12:00""#
+            ));
+            // Отсутствующее поле проходит только отрицания
+            entry.program = None;
+            let matches = |text: &str| Query::parse(text, now).unwrap().matches(&entry);
+            assert!(!matches("program~dbus"));
+            assert!(matches("program!=dbus-daemon"));
+
+            let error = |text: &str| Query::parse(text, now).unwrap_err();
+            assert_eq!(error("level>=warning and prgram=dbus").column, 20);
+            assert_eq!(error("level~warn").column, 6);
+            assert_eq!(error("level=loud").column, 7);
+            assert_eq!(error("pid=12 host=astra").column, 8);
+            assert_eq!(error("(pid=1 or pid=2").column, 16);
+            assert_eq!(error("pid=1)").column, 6);
+            assert_eq!(error("msg~\"(a\"").column, 5);
+            assert_eq!(error("msg=\"open").column, 5);
+            assert_eq!(error("program=").column, 9);
+            assert_eq!(error("").column, 1);
+            assert_eq!(error("pid=x").pointer(), "    ^");
+        }
+
+        #[test]
+        fn test_app_filter_bar() {
+            use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+            let key = |code| KeyEvent::new(code, KeyModifiers::NONE);
+            let type_text = |app: &mut App, text: &str| {
+                for c in text.chars() {
+                    app.handle_key(key(KeyCode::Char(c)));
+                }
+            };
+
+            let mut app = App::new(Logs::new(), LogStats::new(), None);
+            for pid in [1, 2, 3] {
+                let mut entry = LogEntry::new(
+                    LogFormatter::Error,
+                    format!("app[{}]: failed", pid),
+                    None,
+                    PathBuf::from("app.log"),
+                );
+                entry.pid = Some(pid);
+                app.push(entry);
+            }
+            app.set_merge(true);
+            assert_eq!(app.selected(None), Some(2));
+
+            // `q` в строке фильтра — это текст, а не выход
+            app.handle_key(key(KeyCode::Char('/')));
+            type_text(&mut app, "pid>=2 and q");
+            assert!(app.handle_key(key(KeyCode::Enter)));
+            assert_eq!(app.query_error().map(|err| err.column), Some(12));
+
+            for _ in 0.." and q".len() {
+                app.handle_key(key(KeyCode::Backspace));
+            }
+            assert!(app.query_error().is_none());
+            app.handle_key(key(KeyCode::Enter));
+            assert_eq!(app.selected(None), Some(1));
+            assert_eq!(app.selected(Some(LogFormatter::Error)), Some(1));
+
+            // Esc закрывает строку, не меняя применённый запрос
+            app.handle_key(key(KeyCode::Char('/')));
+            type_text(&mut app, " and pid=9");
+            app.handle_key(key(KeyCode::Esc));
+            assert_eq!(app.selected(None), Some(1));
+
+            // Новые записи при активном запросе попадают в списки, только если подходят
+            for pid in [1, 4] {
+                let mut entry = LogEntry::new(
+                    LogFormatter::Error,
+                    format!("app[{}]: failed", pid),
+                    None,
+                    PathBuf::from("app.log"),
+                );
+                entry.pid = Some(pid);
+                app.push(entry);
+            }
+            assert_eq!(app.selected(None), Some(2));
+            assert_eq!(app.selected(Some(LogFormatter::Error)), Some(2));
+
+            app.handle_key(key(KeyCode::Char('/')));
+            for _ in 0.."pid>=2".len() {
+                app.handle_key(key(KeyCode::Backspace));
+            }
+            app.handle_key(key(KeyCode::Enter));
+            assert_eq!(app.selected(None), Some(4));
+            assert!(!app.handle_key(key(KeyCode::Char('q'))));
+        }
+
+        #[test]
+        fn test_journal_json_parser() {
+            let line = r#"{"__REALTIME_TIMESTAMP":"1711108399000000","PRIORITY":"4","_HOSTNAME":"astra","_COMM":"NetworkManager","_PID":"612","MESSAGE":"dhcp4: lease expired"}"#;
+            let parsed = JournalParser.parse(line);
+            assert_eq!(parsed.level, Some(LogFormatter::Warning));
+            assert_eq!(parsed.body, "dhcp4: lease expired");
+            assert_eq!(parsed.host.as_deref(), Some("astra"));
+            assert_eq!(parsed.program.as_deref(), Some("NetworkManager"));
+            assert_eq!(parsed.pid, Some(612));
+            assert_eq!(
+                parsed.timestamp.map(|ts| ts.timestamp_micros()),
+                Some(1711108399000000)
+            );
+            assert!(parsed
+                .message
+                .unwrap()
+                .ends_with(" astra NetworkManager[612]: dhcp4: lease expired"));
+
+            // Значение не UTF-8 приходит массивом байтов, у ядра нет _COMM
+            let parsed = JournalParser.parse(
+                r#"{"SYSLOG_IDENTIFIER":"kernel","MESSAGE":[117,115,98,255],"PRIORITY":"3"}"#,
+            );
+            assert_eq!(parsed.body, "usb\u{fffd}");
+            assert_eq!(parsed.program.as_deref(), Some("kernel"));
+            assert_eq!(parsed.message.as_deref(), Some("kernel: usb\u{fffd}"));
+            assert_eq!(parsed.classify(), LogFormatter::Error);
+
+            assert_eq!(detect_format(&[line, line]), LogFormat::Journal);
+            assert_eq!(JournalParser.parse("not json").body, "not json");
+        }
+
+        #[test]
+        fn test_archive_parser_old_schema() {
+            // Самая старая версия: только level и message; затем date (время чтения) и file_path
+            let head = vec![
+                r#"{"level":"Warning","message":"Mar 22 11:53:19 astra dbus-daemon[383]: low memory"}"#
+                    .to_string(),
+                r#"{"level":"Error","message":"Mar 22 11:53:20 astra kernel: oops","date":"2024-05-23T19:57:15+03:00","file_path":"./log/daemon.log"}"#
+                    .to_string(),
+            ];
+            let reference = Local.with_ymd_and_hms(2030, 1, 1, 0, 0, 0).unwrap();
+            let mut parser = ArchiveParser::new();
+            parser.prime(&head, reference);
+            assert!(head.iter().all(|line| parser.matches(line)));
+            assert!(!parser.matches(r#"{"MESSAGE":"journal"}"#));
+
+            let classifier = Classifier::default();
+            let analyzer = Analyzer::new(&classifier);
+            let oldest = analyzer.entry(&parser, &head[0], PathBuf::from("json/daemon.json"));
+            // Уровень из архива не пересчитывается
+            assert_eq!(oldest.level, LogFormatter::Warning);
+            assert_eq!(oldest.file_path, PathBuf::from("json/daemon.json"));
+            assert_eq!(oldest.program.as_deref(), Some("dbus-daemon"));
+            assert_eq!(oldest.pid, Some(383));
+            assert_eq!(oldest.body.as_deref(), Some("low memory"));
+            // Экспорт самой старой записи (`"ingested_at":""`) читается как текущая версия:
+            // время события не переносится в ingested_at
+            assert_eq!(oldest.ingested_at, "");
+            let exported = serde_json::to_string(&oldest).unwrap();
+            let mut reparser = ArchiveParser::new();
+            reparser.prime(std::slice::from_ref(&exported), reference);
+            let reimported = analyzer.entry(&reparser, &exported, PathBuf::from("again.json"));
+            assert_eq!(serde_json::to_string(&reimported).unwrap(), exported);
+
+            let old = analyzer.entry(&parser, &head[1], PathBuf::from("json/daemon.json"));
+            assert_eq!(old.level, LogFormatter::Error);
+            assert_eq!(old.file_path, PathBuf::from("./log/daemon.log"));
+            assert_eq!(old.ingested_at, "2024-05-23T19:57:15+03:00");
+            // Год события берётся из времени чтения старой записи, а не из reference
+            let date = old.timestamp().unwrap();
+            assert_eq!(
+                date.naive_local(),
+                Local
+                    .with_ymd_and_hms(2024, 3, 22, 11, 53, 20)
+                    .unwrap()
+                    .naive_local()
+            );
+
+            // Текущая версия читается как есть
+            let mut entry = old.clone();
+            entry.tags = vec!["disk".to_string()];
+            let line = serde_json::to_string(&entry).unwrap();
+            let current = analyzer.entry(&parser, &line, PathBuf::from("other.json"));
+            assert_eq!(serde_json::to_string(&current).unwrap(), line);
+        }
+
+        #[test]
+        fn test_multiline_joiner() {
+            let lines: Vec<String> = [
+                "22/03/24 11:53:18 ERROR  :worker: job failed",
+                "Traceback (most recent call last):",
+                "  File \"worker.py\", line 3, in run",
+                "ValueError: info",
+                "22/03/24 11:53:19 INFO   :worker: retry",
+                "\tindented",
+            ]
+            .iter()
+            .map(|line| line.to_string())
+            .collect();
+            let parser = AppParser::new();
+
+            // Начало записи — заголовок формата, остальное склеивается
+            let joiner = Joiner::new(None, 200, 1 << 16).unwrap();
+            let events: Vec<String> = joiner.join(lines.clone(), &parser).collect();
+            assert_eq!(events.len(), 2);
+            assert_eq!(
+                events[1],
+                "22/03/24 11:53:19 INFO   :worker: retry\n\tindented"
+            );
+
+            // Уровень и время берутся из первой строки, а не из текста трассировки
+            let classifier = Classifier::default();
+            let entry =
+                Analyzer::new(&classifier).entry(&parser, &events[0], PathBuf::from("app.log"));
+            assert_eq!(entry.level, LogFormatter::Error);
+            assert!(entry.timestamp().is_some());
+            assert_eq!(entry.message.lines().count(), 4);
+            assert!(entry.body.unwrap().ends_with("in run\nValueError: info"));
+
+            // Своё начало записи и предел строк
+            let joiner = Joiner::new(Some(r"^\d{2}/"), 2, 1 << 16).unwrap();
+            let events: Vec<String> = joiner.join(lines.clone(), &parser).collect();
+            assert_eq!(
+                events.iter().map(|e| e.lines().count()).collect::<Vec<_>>(),
+                vec![2, 2, 2]
+            );
+
+            // Предел байт: событие закрывается, ничего не теряется
+            let joiner = Joiner::new(None, 200, 60).unwrap();
+            let events: Vec<String> = joiner.join(lines.clone(), &parser).collect();
+            assert_eq!(events.len(), 4);
+            assert_eq!(events.join("\n"), lines.join("\n"));
+
+            // В Xorg отступ стоит после метки времени: список версий ABI — одна запись
+            let xorg: Vec<String> = std::fs::read_to_string("log/Xorg.0.log")
+                .unwrap()
+                .lines()
+                .map(String::from)
+                .collect();
+            let parser = XorgParser::new();
+            let joiner = Joiner::new(None, 200, 1 << 16).unwrap();
+            let events: Vec<String> = joiner.join(xorg, &parser).collect();
+            let abi = events
+                .iter()
+                .find(|event| event.contains("Module ABI versions:"))
+                .unwrap();
+            assert_eq!(abi.lines().count(), 5);
+            assert!(abi.ends_with("\tX.Org Server Extension : 10.0"));
+            assert!(events
+                .iter()
+                .any(|event| event.starts_with("[    14.204] (++) using VT number 7")));
+        }
+
+        #[test]
+        fn test_app_expand_multiline() {
+            use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+            let key = |code| KeyEvent::new(code, KeyModifiers::NONE);
+
+            let mut app = App::new(Logs::new(), LogStats::new(), None);
+            for message in ["oops\n  trace 1\n  trace 2", "single"] {
+                app.push(LogEntry::new(
+                    LogFormatter::Critical,
+                    message.to_string(),
+                    None,
+                    PathBuf::from("kern.log"),
+                ));
+            }
+
+            app.handle_key(key(KeyCode::Up));
+            app.handle_key(key(KeyCode::Enter));
+            assert!(app.is_expanded(0));
+            app.handle_key(key(KeyCode::Enter));
+            assert!(!app.is_expanded(0));
+
+            // В общей ленте выбор свой, но развёрнутая запись та же
+            app.handle_key(key(KeyCode::Char('m')));
+            app.handle_key(key(KeyCode::Home));
+            app.handle_key(key(KeyCode::Enter));
+            assert!(app.is_expanded(0));
+            assert!(!app.is_expanded(1));
+        }
+
+        #[test]
+        fn test_rotation_order() {
+            let paths = [
+                "log/daemon.log",
+                "log/syslog.1.xz",
+                "log/daemon.log.10.gz",
+                "log/daemon.log.1",
+                "log/syslog",
+                "log/daemon.log.2.gz",
+                "log/syslog-20240301.zst",
+            ];
+            let ordered = rotation_order(paths.iter().map(PathBuf::from).collect());
+            let expected: Vec<PathBuf> = [
+                "log/daemon.log.10.gz",
+                "log/daemon.log.2.gz",
+                "log/daemon.log.1",
+                "log/daemon.log",
+                "log/syslog-20240301.zst",
+                "log/syslog.1.xz",
+                "log/syslog",
+            ]
+            .iter()
+            .map(PathBuf::from)
+            .collect();
+            assert_eq!(ordered, expected);
+        }
+
+        #[test]
+        fn test_merge_by_time() {
+            let at = |minute, message: &str, file: &str| {
+                LogEntry::new(
+                    LogFormatter::Info,
+                    message.to_string(),
+                    Some(Local.with_ymd_and_hms(2024, 3, 22, 11, minute, 0).unwrap()),
+                    PathBuf::from(file),
+                )
+            };
+            let untimed = |message: &str, file: &str| {
+                LogEntry::new(
+                    LogFormatter::Info,
+                    message.to_string(),
+                    None,
+                    PathBuf::from(file),
+                )
+            };
+
+            let merged: Vec<LogEntry> = merge_by_time(vec![
+                vec![at(1, "a1", "a"), at(3, "a3", "a"), untimed("a3+", "a")],
+                vec![at(0, "b0", "b"), at(3, "b3", "b"), at(4, "b4", "b")],
+                vec![at(2, "c2", "c"), at(1, "c1", "c")],
+            ])
+            .collect();
+            let messages: Vec<&str> = merged.iter().map(|e| e.message.as_str()).collect();
+            // При равном времени первым идёт первый источник, строка без времени — за своей,
+            // а строки одного источника не переставляются
+            assert_eq!(
+                messages,
+                vec!["b0", "a1", "c2", "c1", "a3", "a3+", "b3", "b4"]
+            );
+            assert_eq!(merged[2].file_path, PathBuf::from("c"));
+
+            // Из каждого источника заранее берётся только одна запись
+            let pulled = std::cell::Cell::new(0);
+            let stream = |file: &'static str| {
+                (0..50)
+                    .map(move |minute| at(minute, "x", file))
+                    .inspect(|_| pulled.set(pulled.get() + 1))
+            };
+            let mut merged = merge_by_time(vec![stream("a"), stream("b")]);
+            assert_eq!(pulled.get(), 2);
+            merged.next();
+            assert_eq!(pulled.get(), 3);
+            assert_eq!(merged.count(), 99);
+        }
+
+        #[test]
+        fn test_spool_spills_to_file() {
+            use crate::spool::SpoolWriter;
+            use std::sync::atomic::AtomicUsize;
+            use std::sync::Mutex;
+            let entries: Vec<LogEntry> = (0..5)
+                .map(|i| {
+                    LogEntry::new(
+                        LogFormatter::Info,
+                        format!("line {i}"),
+                        Some(Local.with_ymd_and_hms(2024, 3, 22, 11, i, 0).unwrap()),
+                        PathBuf::from("a.log"),
+                    )
+                })
+                .collect();
+
+            // В памяти помещаются две записи, остальные уходят во временный файл
+            let memory = AtomicUsize::new(2);
+            let mut spool = SpoolWriter::new(&memory);
+            for entry in &entries {
+                spool.push(entry.clone()).unwrap();
+            }
+            let failed = Mutex::new(None);
+            let read: Vec<String> = spool
+                .finish()
+                .unwrap()
+                .reader(&failed)
+                .map(|entry| serde_json::to_string(&entry).unwrap())
+                .collect();
+            let expected: Vec<String> = entries
+                .iter()
+                .map(|entry| serde_json::to_string(entry).unwrap())
+                .collect();
+            assert_eq!(read, expected);
+            assert!(failed.into_inner().unwrap().is_none());
+        }
+
+        #[test]
+        fn test_line_filter() {
+            let patterns = vec![r"usb \d+-\d+".to_string(), "segfault".to_string()];
+            let filter = LineFilter::new(&patterns, false, false).unwrap();
+            assert!(filter.matches("kernel: usb 1-1: new device"));
+            assert!(filter.matches("app[12]: segfault at 0"));
+            assert!(!filter.matches("app[12]: Segfault at 0"));
+
+            let filter = LineFilter::new(&patterns, true, false).unwrap();
+            assert!(filter.matches("app[12]: Segfault at 0"));
+
+            let filter = LineFilter::new(&patterns, false, true).unwrap();
+            assert!(!filter.matches("kernel: usb 1-1: new device"));
+            assert!(filter.matches("systemd: Started"));
+
+            assert!(LineFilter::default().matches("anything"));
+            assert!(LineFilter::new(&["(".to_string()], false, false).is_err());
+        }
+
+        #[test]
+        fn test_parse_time() {
+            let now = Local.with_ymd_and_hms(2024, 3, 22, 11, 53, 18).unwrap();
+            let at = |h, m, s| Local.with_ymd_and_hms(2024, 3, 22, h, m, s).unwrap();
+
+            assert_eq!(parse_time("now", now).unwrap(), now);
+            assert_eq!(parse_time("2h ago", now).unwrap(), at(9, 53, 18));
+            assert_eq!(parse_time("-30m", now).unwrap(), at(11, 23, 18));
+            assert_eq!(
+                parse_time("yesterday", now).unwrap(),
+                Local.with_ymd_and_hms(2024, 3, 21, 0, 0, 0).unwrap()
+            );
+            assert_eq!(parse_time("today", now).unwrap(), at(0, 0, 0));
+            assert_eq!(parse_time("2024-03-22 10:00", now).unwrap(), at(10, 0, 0));
+            assert_eq!(parse_time("2024-03-22", now).unwrap(), at(0, 0, 0));
+            assert_eq!(parse_time("08:15:30", now).unwrap(), at(8, 15, 30));
+            assert!(parse_time("boot", Local::now()).unwrap() <= Local::now());
+            assert!(parse_time("3 fortnights ago", now).is_err());
+            assert_eq!(
+                parse_time("9999999999999999 s ago", now),
+                Err("time out of range".to_string())
+            );
+            assert!(parse_time("99999999999999999999 weeks ago", now).is_err());
+            assert!(parse_time("-99999999 weeks", now).is_err());
+            assert!(parse_time("soon", now).is_err());
+
+            let range = TimeRange {
+                since: Some(at(10, 0, 0)),
+                until: Some(at(11, 0, 0)),
+            };
+            let mut in_range = false;
+            assert!(!range.admit(Some(at(9, 0, 0).fixed_offset()), &mut in_range));
+            assert!(!range.admit(None, &mut in_range));
+            assert!(range.admit(Some(at(10, 0, 0).fixed_offset()), &mut in_range));
+            assert!(range.admit(None, &mut in_range));
+            assert!(range.is_after(&at(11, 0, 1)));
+        }
+
+        #[test]
+        fn test_app_parser_parse() {
+            let parsed =
+                AppParser::new().parse("02/03/22 08:51:01 INFO   :.main: UsinG  loG  level 511");
+            assert_eq!(parsed.level, Some(LogFormatter::Info));
+            assert_eq!(parsed.body, ".main: UsinG  loG  level 511");
+        }
+
+        #[test]
+        fn test_syslog_timestamp_year_inference() {
+            let mut parser = SyslogParser::new();
+            let reference = Local.with_ymd_and_hms(2024, 1, 10, 12, 0, 0).unwrap();
+            parser.prime(&[], reference);
+
+            let parsed = parser.parse("Jan  9 08:00:00 astra cron[12]: job started");
+            assert_eq!(
+                parsed.timestamp,
+                Some(Local.with_ymd_and_hms(2024, 1, 9, 8, 0, 0).unwrap())
+            );
+
+            // Декабрьская запись в январском файле относится к прошлому году
+            let parsed = parser.parse("Dec 31 23:59:59 astra cron[12]: job started");
+            assert_eq!(
+                parsed.timestamp,
+                Some(Local.with_ymd_and_hms(2023, 12, 31, 23, 59, 59).unwrap())
+            );
+
+            assert_eq!(parser.parse("no header").timestamp, None);
+        }
     }
 
     // System Tests
@@ -80,6 +765,900 @@ mod tests {
             assert_eq!(logs.total_messages, 4);
         }
 
+        #[test]
+        fn test_detect_format() {
+            let sniff = |path: &str| {
+                let content = std::fs::read_to_string(path).unwrap();
+                detect_format(&content.lines().collect::<Vec<_>>())
+            };
+
+            assert_eq!(sniff("log/daemon.log"), LogFormat::Syslog);
+            assert_eq!(sniff("log/Xorg.0.log"), LogFormat::Xorg);
+            assert_eq!(sniff("log/a.log"), LogFormat::App);
+            assert_eq!(sniff("log/daemon.json"), LogFormat::Plain);
+        }
+
+        #[test]
+        fn test_xorg_timestamp_anchor() {
+            let content = std::fs::read_to_string("log/Xorg.0.log").unwrap();
+            let head: Vec<String> = content.lines().take(40).map(String::from).collect();
+            let mut parser = XorgParser::new();
+            parser.prime(&head, Local::now());
+
+            // [    14.187] соответствует "Time: Wed Mar 22 11:53:24 2023"
+            let parsed = parser.parse("[    15.187] (II) event");
+            assert_eq!(
+                parsed.timestamp,
+                Some(Local.with_ymd_and_hms(2023, 3, 22, 11, 53, 25).unwrap())
+            );
+            assert_eq!(parser.parse("\tcontinuation").timestamp, None);
+        }
+
+        #[tokio::test]
+        async fn test_syslog_fields_in_json() {
+            let classifier = Classifier::default();
+            let mut logs = Logs::new();
+            let mut analyzer = Analyzer::new(&classifier);
+            analyzer.add_consumer(&mut logs);
+            analyzer
+                .analyze_line(
+                    &SyslogParser::new(),
+                    "Mar 22 11:53:18 astra dbus-daemon[383]: [system] Activating service",
+                    PathBuf::from("/test/path"),
+                )
+                .unwrap();
+            drop(analyzer);
+
+            let entry = &logs.entries[0];
+            assert_eq!(entry.level, LogFormatter::Trace);
+            assert_eq!(entry.host.as_deref(), Some("astra"));
+            assert_eq!(entry.program.as_deref(), Some("dbus-daemon"));
+            assert_eq!(entry.pid, Some(383));
+            assert_eq!(entry.body.as_deref(), Some("[system] Activating service"));
+
+            let output_path = "/tmp/test_syslog_fields.json";
+            logs.format_to_json(output_path).await.unwrap();
+            let file_content = std::fs::read_to_string(output_path).unwrap();
+            assert!(file_content.contains("\"program\": \"dbus-daemon\""));
+            assert!(file_content.contains("\"pid\": 383"));
+        }
+
+        #[tokio::test]
+        async fn test_rules_applied_to_logs_and_stats() {
+            let rules = RuleSet::load(std::path::Path::new("rules.example.toml")).unwrap();
+            let classifier = Classifier::new(rules);
+            let parser = SyslogParser::new();
+            let line = "Mar 22 11:53:18 astra avahi-daemon[439]: WARNING: No NSS support for mDNS detected";
+
+            let mut logs = Logs::new();
+            let mut stats = LogStats::new();
+            let mut analyzer = Analyzer::new(&classifier);
+            analyzer.add_consumer(&mut logs);
+            analyzer.add_consumer(&mut stats);
+            analyzer
+                .analyze_line(&parser, line, PathBuf::from("/test/path"))
+                .unwrap();
+            drop(analyzer);
+
+            assert_eq!(logs.entries[0].level, LogFormatter::Info);
+            assert_eq!(logs.entries[0].tags, vec!["avahi"]);
+            assert_eq!(stats.info_messages, 1);
+            assert_eq!(stats.warning_messages, 0);
+        }
+
+        #[test]
+        fn test_analyzer_stats_match_entries() {
+            let classifier = Classifier::default();
+            let parser = LogFormat::Xorg.parser();
+            let content = std::fs::read_to_string("log/Xorg.0.log").unwrap();
+
+            let mut logs = Logs::new();
+            let mut stats = LogStats::new();
+            let mut analyzer = Analyzer::new(&classifier);
+            analyzer.add_consumer(&mut logs);
+            analyzer.add_consumer(&mut stats);
+            for line in content.lines() {
+                analyzer
+                    .analyze_line(parser.as_ref(), line, PathBuf::from("log/Xorg.0.log"))
+                    .unwrap();
+            }
+            analyzer.finish().unwrap();
+            drop(analyzer);
+
+            assert_eq!(stats.total_messages, logs.entries.len());
+            for level in LogFormatter::ALL {
+                let entries = logs.entries.iter().filter(|e| e.level == level).count();
+                assert_eq!(stats.count(level), entries);
+            }
+        }
+
+        #[test]
+        fn test_level_filter_applies_to_consumers() {
+            let classifier = Classifier::default();
+            let parser = PlainParser;
+            let mut stats = LogStats::new();
+            let mut logs = Logs::new();
+            let mut analyzer = Analyzer::new(&classifier);
+            analyzer.set_level_filter(Some("warning+".parse().unwrap()));
+            analyzer.add_consumer(&mut stats);
+            analyzer.add_consumer(&mut logs);
+
+            // Слово "error" в имени файла больше не влияет на фильтр: важен уровень
+            for line in [
+                "error: disk failure",
+                "warning: low space",
+                "rotated errorlog.txt",
+                "info: started",
+            ] {
+                analyzer
+                    .analyze_line(&parser, line, PathBuf::from("test.log"))
+                    .unwrap();
+            }
+            drop(analyzer);
+
+            assert_eq!(stats.total_messages, 2);
+            assert_eq!(stats.error_messages, 1);
+            assert_eq!(stats.warning_messages, 1);
+            assert_eq!(logs.entries.len(), 2);
+        }
+
+        #[test]
+        fn test_level_filter_keeps_more_severe_lines() {
+            // `-l error+` отбирает записи по уровню, а не по слову "error" в строке
+            let dir = TempDir::new("severe");
+            let path = dir.join("severe.log");
+            std::fs::write(
+                &path,
+                "kernel panic - not syncing\n\
+                 alert: raid degraded\n\
+                 fatal: out of memory\n\
+                 error: disk failure\n\
+                 warning: low space\n\
+                 info: errors=0\n",
+            )
+            .unwrap();
+
+            let classifier = Classifier::default();
+            let lines = LineFilter::default();
+            let reading = Reading {
+                classifier: &classifier,
+                format: Some(LogFormat::Plain),
+                lines: &lines,
+                levels: Some("error+".parse().unwrap()),
+                query: None,
+                range: TimeRange::default(),
+                joiner: None,
+            };
+            let mut levels = Vec::new();
+            reading
+                .read(&Source::File(path.clone()), |entry| {
+                    levels.push(entry.level);
+                    Ok(())
+                })
+                .unwrap();
+
+            assert_eq!(
+                levels,
+                [
+                    LogFormatter::Emergency,
+                    LogFormatter::Alert,
+                    LogFormatter::Critical,
+                    LogFormatter::Error
+                ]
+            );
+        }
+
+        #[test]
+        fn test_json_writer_streams_entries() {
+            let entries: Vec<LogEntry> = ["first", "second error"]
+                .iter()
+                .map(|message| {
+                    LogEntry::new(
+                        LogFormatter::Info,
+                        message.to_string(),
+                        None,
+                        PathBuf::from("/test/path"),
+                    )
+                })
+                .collect();
+
+            let mut array = JsonWriter::new(Vec::new(), JsonMode::JsonPretty);
+            let mut compact = JsonWriter::new(Vec::new(), JsonMode::Json);
+            let mut ndjson = JsonWriter::new(Vec::new(), JsonMode::Ndjson);
+            for entry in &entries {
+                array.consume(entry).unwrap();
+                compact.consume(entry).unwrap();
+                ndjson.consume(entry).unwrap();
+            }
+            array.finish().unwrap();
+            compact.finish().unwrap();
+            ndjson.finish().unwrap();
+
+            let array = String::from_utf8(array.into_inner().unwrap()).unwrap();
+            assert_eq!(array, serde_json::to_string_pretty(&entries).unwrap());
+
+            let compact = String::from_utf8(compact.into_inner().unwrap()).unwrap();
+            assert_eq!(compact, serde_json::to_string(&entries).unwrap() + "\n");
+
+            let mut empty = JsonWriter::new(Vec::new(), JsonMode::Json);
+            empty.finish().unwrap();
+            assert_eq!(empty.into_inner().unwrap(), b"[]\n");
+
+            assert_eq!(JsonMode::from_path("-"), JsonMode::Ndjson);
+            assert_eq!(JsonMode::from_path("out.jsonl"), JsonMode::Ndjson);
+            assert_eq!(JsonMode::from_path("out.json"), JsonMode::JsonPretty);
+
+            let ndjson = String::from_utf8(ndjson.into_inner().unwrap()).unwrap();
+            let lines: Vec<&str> = ndjson.lines().collect();
+            assert_eq!(lines.len(), 2);
+            assert_eq!(lines[1], serde_json::to_string(&entries[1]).unwrap());
+        }
+
+        #[test]
+        fn test_csv_writer_quotes_fields() {
+            let mut entry = LogEntry::new(
+                LogFormatter::Error,
+                "disk \"sda\" failed, retrying\n  at probe".to_string(),
+                None,
+                PathBuf::from("/var/log/syslog"),
+            );
+            entry.program = Some("kernel".to_string());
+            entry.pid = Some(42);
+
+            let columns = [Column::Pid, Column::Level, Column::Message, Column::Host];
+            let mut csv = CsvWriter::new(Vec::new(), &columns, ',', true);
+            csv.consume(&entry).unwrap();
+            csv.finish().unwrap();
+            let csv = String::from_utf8(csv.into_inner().unwrap()).unwrap();
+            assert_eq!(
+                csv,
+                "pid,level,message,host\r\n\
+                 42,Error,\"disk \"\"sda\"\" failed, retrying\n  at probe\",\r\n"
+            );
+
+            let mut tsv = CsvWriter::new(Vec::new(), &Column::DEFAULT, '\t', false);
+            tsv.consume(&entry).unwrap();
+            tsv.finish().unwrap();
+            let tsv = String::from_utf8(tsv.into_inner().unwrap()).unwrap();
+            assert!(tsv.starts_with("Error\t\t\tkernel\t42\t\"disk"));
+            assert!(tsv.ends_with("\t/var/log/syslog\n"));
+
+            // Без записей остаётся только заголовок
+            let mut empty = CsvWriter::new(Vec::new(), &[Column::Level], ',', true);
+            empty.finish().unwrap();
+            assert_eq!(empty.into_inner().unwrap(), b"level\r\n");
+
+            assert_eq!(delimiter_for("out.tsv"), '\t');
+            assert_eq!(delimiter_for("-"), ',');
+        }
+
+        #[test]
+        fn test_html_report() {
+            let mut report = Report::new();
+            let lines = [
+                (LogFormatter::Error, 0, "disk sda1 failed <retry 1>"),
+                (LogFormatter::Error, 1, "disk sda2 failed <retry 2>"),
+                (LogFormatter::Warning, 90, "low memory"),
+                (LogFormatter::Info, 91, "started"),
+            ];
+            for (level, minute, body) in lines {
+                let time = Local.with_ymd_and_hms(2024, 3, 22, 10, 0, 0).unwrap()
+                    + chrono::Duration::minutes(minute);
+                let mut entry = LogEntry::new(
+                    level,
+                    format!("Mar 22 astra kernel: {}", body),
+                    Some(time),
+                    PathBuf::from("/var/log/syslog"),
+                );
+                entry.program = Some("kernel".to_string());
+                entry.body = Some(body.to_string());
+                report.consume(&entry).unwrap();
+            }
+
+            // Сообщения, отличающиеся только числами, считаются одним
+            let top = report.top_messages(1);
+            assert_eq!(top[0].text, "disk sda# failed <retry #>");
+            assert_eq!(top[0].count, 2);
+            assert_eq!(top[0].level, LogFormatter::Error);
+
+            // Полтора часа укладываются в столбцы по две минуты
+            let histogram = report.histogram().unwrap();
+            assert_eq!(histogram.step_minutes, 2);
+            assert_eq!(histogram.bars.len(), 46);
+            assert_eq!(histogram.bars[0], (2, 0));
+            assert_eq!(histogram.bars[45], (0, 1));
+            assert_eq!(report.rows().len(), 3);
+
+            let mut html = Vec::new();
+            report
+                .write_html(&mut html, &["/var/log/syslog".to_string()], Local::now())
+                .unwrap();
+            let html = String::from_utf8(html).unwrap();
+            assert!(html.contains("disk sda1 failed &lt;retry 1&gt;"));
+            assert!(!html.contains("<retry"));
+            assert!(!html.contains("src=") && !html.contains("href=") && !html.contains("http"));
+            assert!(html.trim_end().ends_with("</html>"));
+        }
+
+        #[tokio::test]
+        async fn test_follower_append_and_rotate() {
+            let temp = TempDir::new("follow");
+            let dir = temp.path().to_path_buf();
+            let path = dir.join("daemon.log");
+            std::fs::write(&path, "first\n").unwrap();
+
+            let mut walker = Walker::new(false, &[], &[]).unwrap();
+            let mut follower = Follower::new(std::slice::from_ref(&path), &mut walker).unwrap();
+            let timeout = std::time::Duration::from_secs(5);
+
+            let batches = tokio::time::timeout(timeout, follower.next_batches())
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(batches[0].lines, vec!["first"]);
+            assert!(batches[0].initial);
+
+            let mut file = std::fs::OpenOptions::new()
+                .append(true)
+                .open(&path)
+                .unwrap();
+            std::io::Write::write_all(&mut file, b"second\nthi").unwrap();
+            let batches = tokio::time::timeout(timeout, follower.next_batches())
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(batches[0].lines, vec!["second"]);
+            assert!(!batches[0].initial);
+
+            // logrotate: старый файл переименован и дописан, на его месте новый
+            std::io::Write::write_all(&mut file, b"rd\n").unwrap();
+            std::fs::rename(&path, dir.join("daemon.log.1")).unwrap();
+            std::fs::write(&path, "fresh\n").unwrap();
+            let mut lines = Vec::new();
+            while !lines.contains(&String::from("fresh")) {
+                for batch in tokio::time::timeout(timeout, follower.next_batches())
+                    .await
+                    .unwrap()
+                    .unwrap()
+                {
+                    lines.extend(batch.lines);
+                }
+            }
+            assert_eq!(lines, vec!["third", "fresh"]);
+
+            // copytruncate
+            std::fs::write(&path, "").unwrap();
+            let batches = tokio::time::timeout(timeout, follower.next_batches())
+                .await
+                .unwrap()
+                .unwrap();
+            assert!(batches[0].rotated);
+            std::fs::write(&path, "again\n").unwrap();
+            let batches = tokio::time::timeout(timeout, follower.next_batches())
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(batches[0].lines, vec!["again"]);
+            drop((follower, file));
+
+            // Директория обходится с фильтрами Walker: поддиректории с -r,
+            // --exclude и пропуск двоичных файлов
+            std::fs::create_dir_all(dir.join("sub")).unwrap();
+            std::fs::write(dir.join("sub/nested.log"), "nested\n").unwrap();
+            std::fs::write(dir.join("skip.json"), "{}\n").unwrap();
+            std::fs::write(dir.join("wtmp"), b"\x07\x00\x00\x00tty1\n").unwrap();
+            let mut walker = Walker::new(true, &[], &["*.json".to_string()]).unwrap();
+            let rotated = dir.join("daemon.log.1");
+            let mut follower = Follower::new(std::slice::from_ref(&dir), &mut walker).unwrap();
+            let mut paths = std::collections::BTreeMap::new();
+            while paths.values().map(Vec::len).sum::<usize>() < 5 {
+                for batch in tokio::time::timeout(timeout, follower.next_batches())
+                    .await
+                    .unwrap()
+                    .unwrap()
+                {
+                    paths
+                        .entry(batch.path)
+                        .or_insert_with(Vec::new)
+                        .extend(batch.lines);
+                }
+            }
+            assert_eq!(
+                paths.keys().cloned().collect::<Vec<_>>(),
+                vec![
+                    dir.join("daemon.log"),
+                    rotated.clone(),
+                    dir.join("sub/nested.log")
+                ]
+            );
+
+            // Файл, удалённый из директории, дочитывается и закрывается
+            let mut old = std::fs::OpenOptions::new()
+                .append(true)
+                .open(&rotated)
+                .unwrap();
+            std::io::Write::write_all(&mut old, b"last words\n").unwrap();
+            drop(old);
+            std::fs::remove_file(&rotated).unwrap();
+            let batches = tokio::time::timeout(timeout, follower.next_batches())
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(batches[0].path, rotated);
+            assert_eq!(batches[0].lines, vec!["last words"]);
+            #[cfg(target_os = "linux")]
+            assert!(!std::fs::read_dir("/proc/self/fd")
+                .unwrap()
+                .flatten()
+                .filter_map(|fd| std::fs::read_link(fd.path()).ok())
+                .any(|target| target
+                    .to_string_lossy()
+                    .starts_with(&*rotated.to_string_lossy())));
+            drop(follower);
+            assert_eq!(walker.warnings.len(), 1);
+            assert_eq!(walker.warnings[0].path, dir.join("wtmp"));
+        }
+
+        #[test]
+        fn test_open_compressed_logs() {
+            use std::io::{BufRead, Write};
+            let dir = TempDir::new("compressed");
+            let content = "first line\nsecond line\n";
+
+            let mut gzip = flate2::write::GzEncoder::new(Vec::new(), Default::default());
+            gzip.write_all(content.as_bytes()).unwrap();
+            let mut xz = xz2::write::XzEncoder::new(Vec::new(), 6);
+            xz.write_all(content.as_bytes()).unwrap();
+            let mut bzip2 = bzip2::write::BzEncoder::new(Vec::new(), Default::default());
+            bzip2.write_all(content.as_bytes()).unwrap();
+
+            // Имена без расширений: формат определяется только по сигнатуре
+            let files = [
+                ("plain", content.as_bytes().to_vec(), Compression::None),
+                ("gzip", gzip.finish().unwrap(), Compression::Gzip),
+                ("xz", xz.finish().unwrap(), Compression::Xz),
+                ("bzip2", bzip2.finish().unwrap(), Compression::Bzip2),
+                (
+                    "zstd",
+                    zstd::encode_all(content.as_bytes(), 0).unwrap(),
+                    Compression::Zstd,
+                ),
+            ];
+
+            for (name, data, compression) in files {
+                let path = dir.join(name);
+                std::fs::write(&path, data).unwrap();
+                assert_eq!(Compression::detect(&path).unwrap(), compression);
+                let lines: Vec<String> = open_log(&path)
+                    .unwrap()
+                    .lines()
+                    .map(Result::unwrap)
+                    .collect();
+                assert_eq!(lines, vec!["first line", "second line"], "{}", name);
+            }
+        }
+
+        #[test]
+        fn test_open_reader_stream() {
+            use std::io::{BufRead, Write};
+            // Поток без пути (как stdin): сжатие определяется по первым байтам
+            let mut gzip = flate2::write::GzEncoder::new(Vec::new(), Default::default());
+            gzip.write_all(b"Mar 22 11:53:18 astra kernel: error one\n")
+                .unwrap();
+            let reader = open_reader(std::io::Cursor::new(gzip.finish().unwrap())).unwrap();
+            let lines: Vec<String> = reader.lines().map(Result::unwrap).collect();
+            assert_eq!(lines, vec!["Mar 22 11:53:18 astra kernel: error one"]);
+
+            let reader = open_reader(std::io::Cursor::new(b"plain\n".to_vec())).unwrap();
+            let lines: Vec<String> = reader.lines().map(Result::unwrap).collect();
+            assert_eq!(lines, vec!["plain"]);
+        }
+
+        #[test]
+        fn test_invalid_utf8_lines_are_kept() {
+            let dir = TempDir::new("utf8");
+            let path = dir.join("utf8.log");
+            // Испорченный байт после первых строк, по которым определяется формат, и внутри них
+            let mut content = Vec::new();
+            for i in 0..32 {
+                match i {
+                    5 | 25 => {
+                        content.extend_from_slice(b"Mar 22 11:53:18 astra app: bad \xff byte\n")
+                    }
+                    26 => content.extend_from_slice(b"Mar 22 11:53:19 astra app: error after\r\n"),
+                    _ => content.extend_from_slice(
+                        format!("Mar 22 11:53:18 astra app: line {}\n", i).as_bytes(),
+                    ),
+                }
+            }
+            std::fs::write(&path, &content).unwrap();
+
+            let classifier = Classifier::default();
+            let lines = LineFilter::default();
+            let reading = Reading {
+                classifier: &classifier,
+                format: None,
+                lines: &lines,
+                levels: None,
+                query: None,
+                range: TimeRange::default(),
+                joiner: None,
+            };
+            let mut messages = Vec::new();
+            reading
+                .read(&Source::File(path.clone()), |entry| {
+                    messages.push(entry.message);
+                    Ok(())
+                })
+                .unwrap();
+
+            assert_eq!(messages.len(), 32);
+            assert_eq!(messages[5], "Mar 22 11:53:18 astra app: bad \u{fffd} byte");
+            assert_eq!(messages[25], messages[5]);
+            assert_eq!(messages[26], "Mar 22 11:53:19 astra app: error after");
+            assert_eq!(messages[31], "Mar 22 11:53:18 astra app: line 31");
+
+            // Обрезанный архив: записи до обрыва отдаются, а ошибка не теряется
+            let gz_path = dir.join("utf8.log.gz");
+            let mut gzip = flate2::write::GzEncoder::new(Vec::new(), Default::default());
+            std::io::Write::write_all(&mut gzip, &content).unwrap();
+            let compressed = gzip.finish().unwrap();
+            std::fs::write(&gz_path, &compressed[..compressed.len() - 12]).unwrap();
+            let mut read = 0;
+            let result = reading.read(&Source::File(gz_path.clone()), |_| {
+                read += 1;
+                Ok(())
+            });
+            assert!(result.is_err());
+            assert!(read > 0);
+        }
+
+        #[test]
+        fn test_labeled_sources() {
+            use crate::ingest::split_label;
+            let dir = TempDir::new("label");
+            // Файл с `=` в имени остаётся путём
+            let odd = dir.join("a=b.log");
+            std::fs::write(&odd, "Mar 22 11:53:18 astra app: error one\n").unwrap();
+
+            let arg = PathBuf::from(format!("host1={}", odd.display()));
+            assert_eq!(split_label(&arg), (Some("host1".to_string()), odd.clone()));
+            assert_eq!(split_label(&odd), (None, odd.clone()));
+            assert_eq!(
+                split_label(&PathBuf::from("journal=-")),
+                (Some("journal".to_string()), PathBuf::from("-"))
+            );
+            let missing = PathBuf::from("host1=log/missing.log");
+            assert_eq!(split_label(&missing), (None, missing.clone()));
+
+            let classifier = Classifier::default();
+            let lines = LineFilter::default();
+            let reading = Reading {
+                classifier: &classifier,
+                format: None,
+                lines: &lines,
+                levels: None,
+                query: None,
+                range: TimeRange::default(),
+                joiner: None,
+            };
+            let mut paths = Vec::new();
+            reading
+                .read(&Source::Labeled(odd, "host1".to_string()), |entry| {
+                    paths.push(entry.file_path);
+                    Ok(())
+                })
+                .unwrap();
+            assert_eq!(paths, vec![PathBuf::from("host1")]);
+        }
+
+        #[cfg(unix)]
+        #[test]
+        fn test_walker_recursive_filters() {
+            let temp = TempDir::new("walk");
+            let root = temp.path();
+            std::fs::create_dir_all(root.join("apt/nested")).unwrap();
+            std::fs::write(root.join("syslog"), "text\n").unwrap();
+            std::fs::write(root.join("syslog.1"), "older\n").unwrap();
+            std::fs::write(root.join("report.json"), "{}\n").unwrap();
+            std::fs::write(root.join("wtmp"), [0u8, 1, 2, 3]).unwrap();
+            std::fs::write(root.join("apt/history.log"), "text\n").unwrap();
+            std::fs::write(root.join("apt/nested/term.log"), "text\n").unwrap();
+            // Ссылка на родителя образует цикл
+            std::os::unix::fs::symlink(root, root.join("apt/loop")).unwrap();
+
+            let mut walker = Walker::new(false, &[], &[]).unwrap();
+            let files = walker.files(root);
+            assert_eq!(
+                files,
+                vec![
+                    root.join("report.json"),
+                    root.join("syslog.1"),
+                    root.join("syslog")
+                ]
+            );
+            assert_eq!(walker.warnings.len(), 1);
+            assert_eq!(walker.warnings[0].path, root.join("wtmp"));
+
+            let mut walker = Walker::new(true, &[], &["*.json".to_string()]).unwrap();
+            let files = walker.files(root);
+            assert_eq!(
+                files,
+                vec![
+                    root.join("syslog.1"),
+                    root.join("syslog"),
+                    root.join("apt/history.log"),
+                    root.join("apt/nested/term.log"),
+                ]
+            );
+            assert!(walker.warnings.iter().any(|warning| {
+                warning.path == root.join("apt/loop") && warning.reason == "symlink loop skipped"
+            }));
+
+            // Пересекающиеся корни (`-p root -p root/apt`) — не цикл: вложенная
+            // директория уже прочитана и второй раз не читается
+            let warnings = walker.warnings.len();
+            assert!(walker.files(&root.join("apt")).is_empty());
+            assert_eq!(walker.warnings.len(), warnings + 1);
+            assert_eq!(
+                walker.warnings[warnings].reason,
+                "directory already read from another path"
+            );
+
+            let mut walker = Walker::new(true, &["*.log".to_string()], &[]).unwrap();
+            assert_eq!(
+                walker.files(root),
+                vec![
+                    root.join("apt/history.log"),
+                    root.join("apt/nested/term.log")
+                ]
+            );
+        }
+
+        #[test]
+        fn test_parse_sources_is_deterministic() {
+            let classifier = Classifier::default();
+            let sources = vec![
+                Source::File(PathBuf::from("log/daemon.log")),
+                Source::File(PathBuf::from("log/missing.log")),
+                Source::File(PathBuf::from("log/Xorg.0.log")),
+            ];
+            let lines = LineFilter::default();
+            let reading = Reading {
+                classifier: &classifier,
+                format: None,
+                lines: &lines,
+                levels: None,
+                query: None,
+                range: TimeRange::default(),
+                joiner: None,
+            };
+
+            let merge = |sources: &[Source], jobs| {
+                parse_sources(&reading, sources, jobs, |streams| {
+                    merge_by_time(streams)
+                        .map(|entry| format!("{} {}", entry.file_path.display(), entry.message))
+                        .collect::<Vec<String>>()
+                })
+                .unwrap()
+            };
+            let (sequential, results) = merge(&sources, 1);
+            let (parallel, parallel_results) = merge(&sources, 3);
+            assert_eq!(results.len(), 3);
+            assert!(results[1].is_err() && parallel_results[1].is_err());
+            assert!(results[0].is_ok() && results[2].is_ok());
+            assert_eq!(sequential.len(), 3597 + 432);
+            assert_eq!(sequential, parallel);
+
+            // Больше 32 источников сначала сливаются группами
+            let many: Vec<Source> = (0..40)
+                .map(|i| Source::Labeled(PathBuf::from("log/Xorg.0.log"), format!("xorg{i}")))
+                .collect();
+            let (merged, _) = merge(&many, 4);
+            assert_eq!(merged.len(), 40 * 432);
+            assert_eq!(merged, merge(&many, 1).0);
+            // При равном времени источники идут в порядке указания
+            assert!(merged[0].starts_with("xorg0 "));
+            assert!(merged.last().unwrap().starts_with("xorg39 "));
+        }
+
+        #[test]
+        fn test_since_until_seeks_sorted_file() {
+            use crate::ingest::make_parser;
+            use std::fmt::Write;
+            let dir = TempDir::new("since");
+            let path = dir.join("since.log");
+            // Запись каждые 2 секунды, у некоторых — строка-продолжение без даты
+            let mut content = String::new();
+            for i in 0..30000u32 {
+                let t = i * 2;
+                let (h, m, s) = (t / 3600, t / 60 % 60, t % 60);
+                writeln!(
+                    content,
+                    "Jan 10 {h:02}:{m:02}:{s:02} astra app[1]: message {i}"
+                )
+                .unwrap();
+                if i % 50 == 0 || i % 50 == 49 {
+                    writeln!(content, "    continuation {i}").unwrap();
+                }
+            }
+            std::fs::write(&path, &content).unwrap();
+
+            let head: Vec<String> = content.lines().take(10).map(String::from).collect();
+            let parser = make_parser(&head, None, crate::ingest::modified(&path));
+            let time = |line: &str| parser.parse(line).timestamp.unwrap().with_timezone(&Local);
+            let since = time("Jan 10 08:00:00 astra app[1]: x");
+            let until = time("Jan 10 09:00:00 astra app[1]: x");
+
+            let mut file = std::fs::File::open(&path).unwrap();
+            let offset = seek_since(&mut file, parser.as_ref(), since).unwrap();
+            assert!(offset > 0);
+            assert!(content[..offset as usize].ends_with('\n'));
+            assert!(!content[..offset as usize].contains("message 14400\n"));
+
+            let classifier = Classifier::default();
+            let lines = LineFilter::default();
+            let reading = Reading {
+                classifier: &classifier,
+                format: None,
+                lines: &lines,
+                levels: None,
+                query: None,
+                range: TimeRange {
+                    since: Some(since),
+                    until: Some(until),
+                },
+                joiner: None,
+            };
+            let mut messages = Vec::new();
+            reading
+                .read(&Source::File(path.clone()), |entry| {
+                    messages.push(entry.message);
+                    Ok(())
+                })
+                .unwrap();
+
+            let timed: Vec<&String> = messages
+                .iter()
+                .filter(|message| message.contains("message"))
+                .collect();
+            assert_eq!(timed.len(), 1801);
+            assert!(timed[0].ends_with("message 14400"));
+            assert!(timed[1800].ends_with("message 16200"));
+            assert!(messages.iter().any(|m| m.contains("continuation 14400")));
+            assert!(!messages.iter().any(|m| m.contains("continuation 14399")));
+
+            // Окно с начала файла: поиск ничего не дал, и запись после --until,
+            // доставленная с опозданием, не теряется
+            writeln!(content, "Jan 10 00:05:00 astra app[1]: late delivered").unwrap();
+            std::fs::write(&path, &content).unwrap();
+            let mut file = std::fs::File::open(&path).unwrap();
+            let since = time("Jan 10 00:00:00 astra app[1]: x");
+            assert_eq!(seek_since(&mut file, parser.as_ref(), since).unwrap(), 0);
+            let reading = Reading {
+                range: TimeRange {
+                    since: Some(since),
+                    until: Some(time("Jan 10 00:10:00 astra app[1]: x")),
+                },
+                ..reading
+            };
+            let mut messages = Vec::new();
+            reading
+                .read(&Source::File(path.clone()), |entry| {
+                    messages.push(entry.message);
+                    Ok(())
+                })
+                .unwrap();
+            assert!(messages.last().unwrap().ends_with("late delivered"));
+
+            // Вторая половина раньше первой: файл не упорядочен
+            let (first, second) = content.split_at(content.len() / 2);
+            let first_line = second.find('\n').unwrap() + 1;
+            std::fs::write(&path, [&second[first_line..], first].concat()).unwrap();
+            // (без проверки поиск ушёл бы в конец первой половины)
+            let mut file = std::fs::File::open(&path).unwrap();
+            let since = time("Jan 10 16:00:00 astra app[1]: x");
+            assert_eq!(seek_since(&mut file, parser.as_ref(), since).unwrap(), 0);
+        }
+
+        #[test]
+        fn test_archive_round_trip() {
+            use std::io::BufRead;
+            let classifier = Classifier::default();
+            let analyzer = Analyzer::new(&classifier);
+            let lines = [
+                "Mar 22 11:53:15 astra systemd[1]: Started \"Session\" [1], ok",
+                "Mar 22 11:53:16 astra kernel: oops\n  at probe",
+                "Mar 22 11:53:17 astra dbus-daemon[383]: low memory",
+            ];
+            let entries: Vec<LogEntry> = lines
+                .iter()
+                .map(|line| {
+                    analyzer.entry(&SyslogParser::new(), line, PathBuf::from("/var/log/syslog"))
+                })
+                .collect();
+
+            // Два массива подряд (`cat a.json b.json`) и NDJSON дают те же записи
+            let mut pretty = JsonWriter::new(Vec::new(), JsonMode::JsonPretty);
+            let mut ndjson = JsonWriter::new(Vec::new(), JsonMode::Ndjson);
+            for entry in &entries {
+                pretty.consume(entry).unwrap();
+                ndjson.consume(entry).unwrap();
+            }
+            pretty.finish().unwrap();
+            ndjson.finish().unwrap();
+            let pretty = pretty.into_inner().unwrap();
+            let twice = [pretty.clone(), pretty].concat();
+
+            for data in [twice, ndjson.into_inner().unwrap()] {
+                let reader = open_reader(std::io::Cursor::new(data)).unwrap();
+                let lines: Vec<String> = reader.lines().map(Result::unwrap).collect();
+                assert_eq!(detect_format(&lines), LogFormat::Archive);
+
+                let mut parser = LogFormat::Archive.parser();
+                parser.prime(&lines, Local::now());
+                let imported: Vec<String> = lines
+                    .iter()
+                    .map(|line| analyzer.entry(parser.as_ref(), line, PathBuf::from("archive")))
+                    .map(|entry| serde_json::to_string(&entry).unwrap())
+                    .collect();
+                let expected: Vec<String> = entries
+                    .iter()
+                    .map(|entry| serde_json::to_string(entry).unwrap())
+                    .collect();
+                for chunk in imported.chunks(expected.len()) {
+                    assert_eq!(chunk, &expected[..]);
+                }
+            }
+        }
+
+        #[test]
+        fn test_journal_export_reader() {
+            use std::io::BufRead;
+            let mut export = b"__CURSOR=s=1\n__REALTIME_TIMESTAMP=1711108399000000\nPRIORITY=6\n\
+                _HOSTNAME=astra\n_COMM=dbus-daemon\n_PID=453\nMESSAGE=Activating service\n\n\
+                __CURSOR=s=2\n__REALTIME_TIMESTAMP=1711108400000000\nPRIORITY=2\nMESSAGE\n"
+                .to_vec();
+            // Двоичное поле: длина u64 LE, байты с переводом строки внутри, `\n`
+            let message = b"disk\nfailed";
+            export.extend_from_slice(&(message.len() as u64).to_le_bytes());
+            export.extend_from_slice(message);
+            export.extend_from_slice(b"\nSYSLOG_IDENTIFIER=kernel\n");
+
+            // Сжатый экспорт тоже распознаётся после распаковки
+            let reader = open_reader(std::io::Cursor::new(
+                zstd::encode_all(&export[..], 0).unwrap(),
+            ))
+            .unwrap();
+            let lines: Vec<String> = reader.lines().map(Result::unwrap).collect();
+            assert_eq!(lines.len(), 2);
+            assert_eq!(detect_format(&lines), LogFormat::Journal);
+
+            let classifier = Classifier::default();
+            let analyzer = Analyzer::new(&classifier);
+            let entries: Vec<LogEntry> = lines
+                .iter()
+                .map(|line| analyzer.entry(&JournalParser, line, PathBuf::from("journal")))
+                .collect();
+            assert_eq!(entries[0].level, LogFormatter::Info);
+            assert_eq!(entries[0].program.as_deref(), Some("dbus-daemon"));
+            assert_eq!(entries[0].pid, Some(453));
+            assert!(entries[0]
+                .message
+                .ends_with(" astra dbus-daemon[453]: Activating service"));
+            assert_eq!(entries[0].body.as_deref(), Some("Activating service"));
+            assert_eq!(entries[1].level, LogFormatter::Critical);
+            assert_eq!(entries[1].body.as_deref(), Some("disk\nfailed"));
+            assert!(entries[0].timestamp() < entries[1].timestamp());
+
+            // Обрезанное двоичное поле — ошибка чтения, а не мусор в записях
+            let mut truncated = b"__CURSOR=s=1\nMESSAGE\n".to_vec();
+            truncated.extend_from_slice(&100u64.to_le_bytes());
+            truncated.extend_from_slice(b"short");
+            let mut reader = ExportReader::new(std::io::Cursor::new(truncated));
+            assert!(reader.fill_buf().is_err());
+        }
+
         #[tokio::test]
         async fn test_format_to_json() {
             let logs = Logs::new();
@@ -116,6 +1695,11 @@ mod tests {
             stats.analyze_log_line("This is a trace message").await;
             assert_eq!(stats.total_messages, 4);
             assert_eq!(stats.trace_messages, 1);
+
+            stats.analyze_log_line("kernel: notice: disk is full").await;
+            assert_eq!(stats.total_messages, 5);
+            assert_eq!(stats.notice_messages, 1);
+            assert_eq!(stats.count(LogFormatter::Notice), 1);
         }
     }
 }
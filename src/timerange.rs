@@ -0,0 +1,138 @@
+use chrono::{
+    DateTime, Duration, FixedOffset, Local, NaiveDate, NaiveDateTime, NaiveTime, TimeZone,
+};
+use regex::Regex;
+use std::sync::LazyLock;
+
+// "2h ago", "15 minutes ago", "-3d"
+static RELATIVE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^(?:-\s*(?P<n1>\d+)\s*(?P<u1>[a-z]+)|(?P<n2>\d+)\s*(?P<u2>[a-z]+)\s+ago)$")
+        .unwrap()
+});
+
+const DATE_TIME_FORMATS: [&str; 4] = [
+    "%Y-%m-%d %H:%M:%S",
+    "%Y-%m-%dT%H:%M:%S",
+    "%Y-%m-%d %H:%M",
+    "%Y-%m-%dT%H:%M",
+];
+
+const TIME_FORMATS: [&str; 2] = ["%H:%M:%S", "%H:%M"];
+
+// Окно времени для --since/--until; границы включаются
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct TimeRange {
+    pub since: Option<DateTime<Local>>,
+    pub until: Option<DateTime<Local>>,
+}
+
+impl TimeRange {
+    pub fn is_empty(&self) -> bool {
+        self.since.is_none() && self.until.is_none()
+    }
+
+    pub fn contains<Tz: TimeZone>(&self, timestamp: &DateTime<Tz>) -> bool {
+        self.since.is_none_or(|since| *timestamp >= since)
+            && self.until.is_none_or(|until| *timestamp <= until)
+    }
+
+    // Записи без времени (продолжения многострочных сообщений) следуют
+    // решению для предыдущей записи своего источника
+    pub fn admit(&self, timestamp: Option<DateTime<FixedOffset>>, in_range: &mut bool) -> bool {
+        if self.is_empty() {
+            return true;
+        }
+        if let Some(timestamp) = timestamp {
+            *in_range = self.contains(&timestamp);
+        }
+        *in_range
+    }
+
+    // Запись позже конца окна: в упорядоченном файле дальше читать незачем
+    pub fn is_after<Tz: TimeZone>(&self, timestamp: &DateTime<Tz>) -> bool {
+        self.until.is_some_and(|until| *timestamp > until)
+    }
+}
+
+const OUT_OF_RANGE: &str = "time out of range";
+
+fn unit_duration(count: &str, unit: &str) -> Result<Duration, String> {
+    let duration: fn(i64) -> Option<Duration> = match unit {
+        "s" | "sec" | "secs" | "second" | "seconds" => Duration::try_seconds,
+        "m" | "min" | "mins" | "minute" | "minutes" => Duration::try_minutes,
+        "h" | "hour" | "hours" => Duration::try_hours,
+        "d" | "day" | "days" => Duration::try_days,
+        "w" | "week" | "weeks" => Duration::try_weeks,
+        _ => return Err(format!("unknown time unit '{}'", unit)),
+    };
+    count
+        .parse()
+        .ok()
+        .and_then(duration)
+        .ok_or_else(|| OUT_OF_RANGE.to_string())
+}
+
+fn local(naive: NaiveDateTime) -> Option<DateTime<Local>> {
+    Local.from_local_datetime(&naive).earliest()
+}
+
+fn boot_time() -> Option<DateTime<Local>> {
+    Local
+        .timestamp_opt(sysinfo::System::boot_time() as i64, 0)
+        .single()
+}
+
+// Момент времени для --since/--until: абсолютная дата (`2024-03-22`,
+// `2024-03-22 11:53`, RFC 3339), время сегодняшнего дня (`11:53`),
+// относительное выражение (`2h ago`, `-30m`) или `now`, `today`, `yesterday`, `boot`
+pub fn parse_time(text: &str, now: DateTime<Local>) -> Result<DateTime<Local>, String> {
+    let text = text.trim();
+    let lower = text.to_lowercase();
+    let midnight = now.date_naive().and_time(NaiveTime::MIN);
+
+    let parsed = match lower.as_str() {
+        "now" => Some(now),
+        "today" => local(midnight),
+        "yesterday" => local(midnight - Duration::days(1)),
+        "boot" => boot_time(),
+        _ => None,
+    };
+    if let Some(time) = parsed {
+        return Ok(time);
+    }
+
+    if let Some(caps) = RELATIVE.captures(&lower) {
+        let count = caps.name("n1").or(caps.name("n2")).unwrap().as_str();
+        let unit = caps.name("u1").or(caps.name("u2")).unwrap().as_str();
+        return now
+            .checked_sub_signed(unit_duration(count, unit)?)
+            .ok_or_else(|| OUT_OF_RANGE.to_string());
+    }
+
+    if let Ok(time) = DateTime::parse_from_rfc3339(text) {
+        return Ok(time.with_timezone(&Local));
+    }
+
+    let naive = DATE_TIME_FORMATS
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(text, format).ok())
+        .or_else(|| {
+            NaiveDate::parse_from_str(text, "%Y-%m-%d")
+                .ok()
+                .map(|date| date.and_time(NaiveTime::MIN))
+        })
+        .or_else(|| {
+            TIME_FORMATS
+                .iter()
+                .find_map(|format| NaiveTime::parse_from_str(text, format).ok())
+                .map(|time| now.date_naive().and_time(time))
+        });
+
+    naive.and_then(local).ok_or_else(|| {
+        format!(
+            "invalid time '{}': expected a date (2024-03-22 11:53), a time (11:53), \
+             a relative time (2h ago) or now/today/yesterday/boot",
+            text
+        )
+    })
+}
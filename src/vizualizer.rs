@@ -1,20 +1,30 @@
-use crate::formatter::{LogFormatter, Logs};
+use crate::formatter::{LevelFilter, LogEntry, LogFormatter, Logs};
+use crate::query::{Query, QueryError};
 use crate::scanner::LogStats;
 use crossterm::{
-    event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode},
+    event::{
+        DisableMouseCapture, EnableMouseCapture, Event, EventStream, KeyCode, KeyEvent,
+        KeyModifiers,
+    },
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
+use futures::StreamExt;
+use std::collections::{HashMap, HashSet};
 use std::{error::Error, fmt, io};
+use tokio::sync::mpsc::UnboundedReceiver;
 use tui::{
     backend::CrosstermBackend,
     layout::{Constraint, Direction, Layout},
     style::{Color, Modifier, Style},
     text::{Span, Spans, Text},
-    widgets::{Block, Borders, List, ListItem, Paragraph, Tabs, Wrap},
+    widgets::{Block, Borders, List, ListItem, ListState, Paragraph, Tabs, Wrap},
     Terminal,
 };
 
+// Шаг прокрутки для PageUp/PageDown
+const PAGE_SIZE: usize = 10;
+
 // Структура для представления эллиптической кривой
 pub struct EllipticCurve {
     a: f64,
@@ -47,36 +57,377 @@ impl EllipticCurve {
 impl fmt::Display for LogFormatter {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
-            LogFormatter::Info => write!(f, "INFO"),
-            LogFormatter::Warning => write!(f, "WARNING"),
+            LogFormatter::Emergency => write!(f, "EMERGENCY"),
+            LogFormatter::Alert => write!(f, "ALERT"),
+            LogFormatter::Critical => write!(f, "CRITICAL"),
             LogFormatter::Error => write!(f, "ERROR"),
+            LogFormatter::Warning => write!(f, "WARNING"),
+            LogFormatter::Notice => write!(f, "NOTICE"),
+            LogFormatter::Info => write!(f, "INFO"),
+            LogFormatter::Debug => write!(f, "DEBUG"),
             LogFormatter::Trace => write!(f, "TRACE"),
         }
     }
 }
 
+fn level_style(level: LogFormatter) -> Style {
+    match level {
+        LogFormatter::Emergency | LogFormatter::Alert | LogFormatter::Critical => Style::default()
+            .fg(Color::Magenta)
+            .add_modifier(Modifier::BOLD),
+        LogFormatter::Error => Style::default().fg(Color::Red),
+        LogFormatter::Warning => Style::default().fg(Color::Yellow),
+        LogFormatter::Notice => Style::default().fg(Color::Cyan),
+        LogFormatter::Info => Style::default().fg(Color::Blue),
+        LogFormatter::Debug => Style::default().fg(Color::Gray),
+        LogFormatter::Trace => Style::default().fg(Color::White),
+    }
+}
+
+// Цвета источников в общей ленте (--merge), по порядку появления
+const SOURCE_COLORS: [Color; 6] = [
+    Color::Cyan,
+    Color::Green,
+    Color::Yellow,
+    Color::Magenta,
+    Color::LightBlue,
+    Color::LightRed,
+];
+
+// Список на экране: колонка уровня или общая лента всех источников (None)
+type ListKey = Option<LogFormatter>;
+
+// Состояние списка: выбранная запись и автопрокрутка к последней
+struct Column {
+    state: ListState,
+    follow: bool,
+}
+
+impl Default for Column {
+    fn default() -> Self {
+        Column {
+            state: ListState::default(),
+            follow: true,
+        }
+    }
+}
+
 pub struct App {
     logs: Logs,
     stats: LogStats,
-    filter: Option<LogFormatter>,
+    filter: Option<LevelFilter>,
     tab: usize,
     points: Vec<(f64, f64)>,
+    columns: HashMap<ListKey, Column>,
+    // Число записей в каждом списке: новая запись только прибавляет к нему,
+    // целиком списки пересчитываются лишь при смене запроса
+    lengths: HashMap<ListKey, usize>,
+    // Колонка, которую прокручивает пользователь
+    focus: Option<LogFormatter>,
+    // Одна лента по времени вместо колонок по уровням
+    merge: bool,
+    // На паузе новые записи копятся здесь и не сдвигают списки
+    paused: bool,
+    pending: Vec<LogEntry>,
+    // Запрос из строки фильтра (`/`): скрывает записи, но не меняет счётчики
+    query: Option<Query>,
+    query_text: String,
+    // Текст в открытой строке фильтра и ошибка последней попытки его применить
+    input: Option<String>,
+    query_error: Option<QueryError>,
+    // Развёрнутые многострочные записи (индексы в logs.entries)
+    expanded: HashSet<usize>,
 }
 
 impl App {
-    pub fn new(logs: Logs, stats: LogStats, filter: Option<LogFormatter>) -> Self {
-        // Используем значения из stats для a и b
-        let a = stats.error_messages as f64 / stats.total_messages as f64;
-        let b = stats.warning_messages as f64 / stats.total_messages as f64;
-        let curve = EllipticCurve::new(a, b);
-        let points = curve.calculate_points(-5.0, 5.0, 0.1);
-        App {
+    pub fn new(logs: Logs, stats: LogStats, filter: Option<LevelFilter>) -> Self {
+        let mut app = App {
             logs,
             stats,
             filter,
             tab: 0,
-            points,
+            points: Vec::new(),
+            columns: HashMap::new(),
+            lengths: HashMap::new(),
+            focus: None,
+            merge: false,
+            paused: false,
+            pending: Vec::new(),
+            query: None,
+            query_text: String::new(),
+            input: None,
+            query_error: None,
+            expanded: HashSet::new(),
+        };
+        app.update_curve();
+        app.count_lists();
+        for level in LogFormatter::ALL {
+            app.scroll_to_end(Some(level));
+        }
+        app.scroll_to_end(None);
+        app
+    }
+
+    pub fn set_merge(&mut self, merge: bool) {
+        self.merge = merge;
+    }
+
+    pub fn logs(&self) -> &Logs {
+        &self.logs
+    }
+
+    pub fn stats(&self) -> &LogStats {
+        &self.stats
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    // Номер выбранной записи в колонке уровня (None — в общей ленте)
+    pub fn selected(&self, level: Option<LogFormatter>) -> Option<usize> {
+        self.columns
+            .get(&level)
+            .and_then(|column| column.state.selected())
+    }
+
+    pub fn is_expanded(&self, index: usize) -> bool {
+        self.expanded.contains(&index)
+    }
+
+    pub fn query_error(&self) -> Option<&QueryError> {
+        self.query_error.as_ref()
+    }
+
+    // Применяет запрос к уже загруженным записям; пустая строка снимает фильтр
+    pub fn set_query(&mut self, text: &str) -> Result<(), QueryError> {
+        let text = text.trim();
+        self.query = match text {
+            "" => None,
+            text => Some(Query::parse(text, chrono::Local::now())?),
+        };
+        self.query_text = text.to_string();
+        self.count_lists();
+        for level in LogFormatter::ALL {
+            self.scroll_to_end(Some(level));
+        }
+        self.scroll_to_end(None);
+        Ok(())
+    }
+
+    // Новая запись из --follow: счётчики обновляются сразу, списки — если не на паузе
+    pub fn push(&mut self, entry: LogEntry) {
+        self.stats.record(entry.level);
+        if self.paused {
+            self.pending.push(entry);
+        } else {
+            self.append(entry);
+        }
+    }
+
+    fn append(&mut self, entry: LogEntry) {
+        let level = entry.level;
+        for key in [Some(level), None] {
+            if self.in_list(key, &entry) {
+                *self.lengths.entry(key).or_default() += 1;
+            }
         }
+        self.logs.push(entry);
+        for key in [Some(level), None] {
+            if self.columns.get(&key).is_none_or(|column| column.follow) {
+                self.scroll_to_end(key);
+            }
+        }
+    }
+
+    pub fn toggle_pause(&mut self) {
+        self.paused = !self.paused;
+        if !self.paused {
+            for entry in std::mem::take(&mut self.pending) {
+                self.append(entry);
+            }
+        }
+    }
+
+    // Обрабатывает клавишу; возвращает false, если нужно выйти
+    pub fn handle_key(&mut self, key: KeyEvent) -> bool {
+        if self.input.is_some() {
+            return self.handle_input(key);
+        }
+        match key.code {
+            KeyCode::Char('q') => return false,
+            // В raw-режиме Ctrl+C не приходит сигналом
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => return false,
+            KeyCode::Char('t') => self.tab = (self.tab + 1) % 2,
+            KeyCode::Char('m') => self.merge = !self.merge,
+            KeyCode::Char('/') => self.input = Some(self.query_text.clone()),
+            KeyCode::Enter => self.toggle_expanded(),
+            KeyCode::Char(' ') | KeyCode::Char('p') => self.toggle_pause(),
+            KeyCode::Left | KeyCode::Char('h') => self.move_focus(-1),
+            KeyCode::Right | KeyCode::Char('l') => self.move_focus(1),
+            KeyCode::Up | KeyCode::Char('k') => self.scroll(-1),
+            KeyCode::Down | KeyCode::Char('j') => self.scroll(1),
+            KeyCode::PageUp => self.scroll(-(PAGE_SIZE as isize)),
+            KeyCode::PageDown => self.scroll(PAGE_SIZE as isize),
+            KeyCode::Home | KeyCode::Char('g') => self.scroll(isize::MIN),
+            KeyCode::End | KeyCode::Char('G') => {
+                if let Some(key) = self.active_list() {
+                    self.scroll_to_end(key);
+                }
+            }
+            _ => {}
+        }
+        true
+    }
+
+    // Строка фильтра: Enter применяет запрос, Esc закрывает её без изменений
+    fn handle_input(&mut self, key: KeyEvent) -> bool {
+        let input = match self.input.as_mut() {
+            Some(input) => input,
+            None => return true,
+        };
+        match key.code {
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => return false,
+            KeyCode::Char(c) => input.push(c),
+            KeyCode::Backspace => {
+                input.pop();
+            }
+            KeyCode::Esc => self.input = None,
+            KeyCode::Enter => {
+                let text = input.clone();
+                match self.set_query(&text) {
+                    Ok(()) => self.input = None,
+                    Err(err) => {
+                        self.query_error = Some(err);
+                        return true;
+                    }
+                }
+            }
+            _ => return true,
+        }
+        self.query_error = None;
+        true
+    }
+
+    // Индекс выбранной записи активного списка в logs.entries
+    fn selected_entry(&self) -> Option<usize> {
+        let key = self.active_list()?;
+        let selected = self.columns.get(&key)?.state.selected()?;
+        self.logs
+            .entries
+            .iter()
+            .enumerate()
+            .filter(|(_, entry)| self.in_list(key, entry))
+            .nth(selected)
+            .map(|(index, _)| index)
+    }
+
+    fn toggle_expanded(&mut self) {
+        if let Some(index) = self.selected_entry() {
+            if !self.expanded.remove(&index) {
+                self.expanded.insert(index);
+            }
+        }
+    }
+
+    fn visible(&self, entry: &LogEntry) -> bool {
+        self.query.as_ref().is_none_or(|query| query.matches(entry))
+    }
+
+    // Уровни, для которых показываются колонки
+    fn levels(&self) -> Vec<LogFormatter> {
+        LogFormatter::ALL
+            .into_iter()
+            .filter(|level| self.filter.is_none_or(|filter| filter.matches(*level)))
+            .filter(|level| self.stats.count(*level) > 0)
+            .collect()
+    }
+
+    fn focused(&self) -> Option<LogFormatter> {
+        let levels = self.levels();
+        self.focus
+            .filter(|level| levels.contains(level))
+            .or_else(|| levels.first().copied())
+    }
+
+    fn move_focus(&mut self, delta: isize) {
+        let levels = self.levels();
+        if levels.is_empty() {
+            return;
+        }
+        let current = self
+            .focused()
+            .and_then(|level| levels.iter().position(|l| *l == level))
+            .unwrap_or(0);
+        let next = (current as isize + delta).clamp(0, levels.len() as isize - 1);
+        self.focus = Some(levels[next as usize]);
+    }
+
+    // Список, который сейчас прокручивается
+    fn active_list(&self) -> Option<ListKey> {
+        if self.merge {
+            Some(None)
+        } else {
+            self.focused().map(Some)
+        }
+    }
+
+    fn in_list(&self, key: ListKey, entry: &LogEntry) -> bool {
+        if !self.visible(entry) {
+            return false;
+        }
+        match key {
+            Some(level) => entry.level == level,
+            None => self.filter.is_none_or(|filter| filter.matches(entry.level)),
+        }
+    }
+
+    fn len(&self, key: ListKey) -> usize {
+        self.lengths.get(&key).copied().unwrap_or(0)
+    }
+
+    fn count_lists(&mut self) {
+        let mut lengths = HashMap::new();
+        for entry in &self.logs.entries {
+            for key in [Some(entry.level), None] {
+                if self.in_list(key, entry) {
+                    *lengths.entry(key).or_default() += 1;
+                }
+            }
+        }
+        self.lengths = lengths;
+    }
+
+    fn scroll(&mut self, delta: isize) {
+        let key = match self.active_list() {
+            Some(key) => key,
+            None => return,
+        };
+        let len = self.len(key);
+        if len == 0 {
+            return;
+        }
+        let column = self.columns.entry(key).or_default();
+        let current = column.state.selected().unwrap_or(len - 1) as isize;
+        let next = current.saturating_add(delta).clamp(0, len as isize - 1) as usize;
+        column.state.select(Some(next));
+        // Автопрокрутка продолжается, только если пользователь вернулся в конец
+        column.follow = next == len - 1;
+    }
+
+    fn scroll_to_end(&mut self, key: ListKey) {
+        let len = self.len(key);
+        let column = self.columns.entry(key).or_default();
+        column.state.select(len.checked_sub(1));
+        column.follow = true;
+    }
+
+    fn update_curve(&mut self) {
+        // Используем значения из stats для a и b
+        let a = self.stats.error_messages as f64 / self.stats.total_messages as f64;
+        let b = self.stats.warning_messages as f64 / self.stats.total_messages as f64;
+        let curve = EllipticCurve::new(a, b);
+        self.points = curve.calculate_points(-5.0, 5.0, 0.1);
     }
 
     pub fn render(
@@ -84,10 +435,26 @@ impl App {
         terminal: &mut Terminal<CrosstermBackend<io::Stdout>>,
     ) -> Result<(), Box<dyn Error>> {
         let size = terminal.size()?;
+        // Строка фильтра видна, пока её редактируют или пока запрос применён;
+        // при ошибке под текстом появляется строка с `^` и описанием
+        let bar = match (&self.input, &self.query_error) {
+            (Some(_), Some(_)) => 4,
+            (Some(_), None) => 3,
+            (None, _) if self.query.is_some() => 3,
+            (None, _) => 0,
+        };
         let chunks = Layout::default()
             .direction(Direction::Vertical)
-            .constraints([Constraint::Length(3), Constraint::Min(80)].as_ref())
+            .constraints(
+                [
+                    Constraint::Length(3),
+                    Constraint::Min(0),
+                    Constraint::Length(bar),
+                ]
+                .as_ref(),
+            )
             .split(size);
+        let filter_bar = self.filter_bar();
 
         let titles = ["Logs", "Elliptic Curve"]
             .iter()
@@ -108,107 +475,199 @@ impl App {
         terminal.draw(|f| {
             f.render_widget(tabs, chunks[0]);
             match self.tab {
+                0 if self.merge => self.render_merged(f, chunks[1]),
                 0 => self.render_logs(f, chunks[1]),
                 1 => self.render_curve(f, chunks[1]),
                 _ => {}
             }
+            if bar > 0 {
+                f.render_widget(filter_bar, chunks[2]);
+            }
         })?;
 
         Ok(())
     }
 
     fn render_logs<B: tui::backend::Backend>(
-        &self,
+        &mut self,
         f: &mut tui::Frame<B>,
         area: tui::layout::Rect,
     ) {
+        // Колонка на каждый уровень, который прошёл фильтр и встречается в логах
+        let levels = self.levels();
+        let focus = self.focused();
+
+        let constraints: Vec<Constraint> = levels
+            .iter()
+            .map(|_| Constraint::Ratio(1, levels.len() as u32))
+            .collect();
+
         let log_chunks = Layout::default()
             .direction(Direction::Horizontal)
-            .constraints(if self.filter.is_some() {
-                [Constraint::Percentage(100)].as_ref()
-            } else {
-                [
-                    Constraint::Percentage(25),
-                    Constraint::Percentage(25),
-                    Constraint::Percentage(25),
-                    Constraint::Percentage(25),
-                ]
-                .as_ref()
-            })
+            .constraints(constraints)
             .split(area);
 
-        let mut info_items: Vec<ListItem> = Vec::new();
-        let mut warning_items: Vec<ListItem> = Vec::new();
-        let mut error_items: Vec<ListItem> = Vec::new();
-        let mut trace_items: Vec<ListItem> = Vec::new();
+        let mut items: Vec<Vec<ListItem>> = levels.iter().map(|_| Vec::new()).collect();
 
-        for entry in &self.logs.entries {
-            let level = match entry.level {
-                LogFormatter::Info => Style::default().fg(Color::Blue),
-                LogFormatter::Warning => Style::default().fg(Color::Yellow),
-                LogFormatter::Error => Style::default().fg(Color::Red),
-                LogFormatter::Trace => Style::default().fg(Color::White),
+        for (index, entry) in self.logs.entries.iter().enumerate() {
+            if !self.visible(entry) {
+                continue;
+            }
+            let column = match levels.iter().position(|level| *level == entry.level) {
+                Some(column) => column,
+                None => continue,
             };
 
-            let message = Spans::from(vec![Span::styled(entry.message.clone(), level)]);
+            let level = level_style(entry.level);
+
+            let message = message_lines(&entry.message, self.expanded.contains(&index), level);
             let metadata = Spans::from(vec![
                 Span::styled(format!("{}", entry.level), level),
                 Span::raw(" "),
                 Span::styled(
-                    entry.date.clone(),
+                    entry
+                        .date
+                        .clone()
+                        .unwrap_or_else(|| String::from("no timestamp")),
                     Style::default().add_modifier(Modifier::ITALIC),
                 ),
             ]);
 
-            let item = ListItem::new(vec![metadata, Spans::from(""), message]);
+            let mut lines = vec![metadata, Spans::from("")];
+            lines.extend(message);
+            items[column].push(ListItem::new(lines));
+        }
 
-            match entry.level {
-                LogFormatter::Info => info_items.push(item),
-                LogFormatter::Warning => warning_items.push(item),
-                LogFormatter::Error => error_items.push(item),
-                LogFormatter::Trace => trace_items.push(item),
+        let stats_list = self.stats_paragraph();
+
+        for ((level, items), chunk) in levels.iter().zip(items).zip(log_chunks) {
+            let mut list = List::new(items).block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title(format!("{} Logs", level.title())),
+            );
+            if focus == Some(*level) {
+                list = list.highlight_style(Style::default().add_modifier(Modifier::REVERSED));
             }
+            let column = self.columns.entry(Some(*level)).or_default();
+            f.render_stateful_widget(list, chunk, &mut column.state);
         }
+        f.render_widget(stats_list, area);
+    }
 
-        let info_list =
-            List::new(info_items).block(Block::default().borders(Borders::ALL).title("Info Logs"));
+    // Все источники в одной ленте по времени; каждый источник своим цветом
+    fn render_merged<B: tui::backend::Backend>(
+        &mut self,
+        f: &mut tui::Frame<B>,
+        area: tui::layout::Rect,
+    ) {
+        let mut sources: Vec<&std::path::Path> = Vec::new();
+        let mut items = Vec::new();
 
-        let warning_list = List::new(warning_items)
-            .block(Block::default().borders(Borders::ALL).title("Warning Logs"));
+        for (index, entry) in self.logs.entries.iter().enumerate() {
+            if !self.in_list(None, entry) {
+                continue;
+            }
 
-        let error_list = List::new(error_items)
-            .block(Block::default().borders(Borders::ALL).title("Error Logs"));
+            let color = match sources.iter().position(|path| *path == entry.file_path) {
+                Some(color) => color,
+                None => {
+                    sources.push(&entry.file_path);
+                    sources.len() - 1
+                }
+            };
+            let source = Style::default().fg(SOURCE_COLORS[color % SOURCE_COLORS.len()]);
 
-        let trace_list = List::new(trace_items)
-            .block(Block::default().borders(Borders::ALL).title("Trace Logs"));
+            let prefix = vec![
+                Span::styled(format!("[{}]", source_name(&entry.file_path)), source),
+                Span::raw(" "),
+                Span::styled(format!("{}", entry.level), level_style(entry.level)),
+                Span::raw(" "),
+                Span::styled(
+                    entry
+                        .date
+                        .clone()
+                        .unwrap_or_else(|| String::from("no timestamp")),
+                    Style::default().add_modifier(Modifier::ITALIC),
+                ),
+                Span::raw(" "),
+            ];
+            let mut lines = message_lines(&entry.message, self.expanded.contains(&index), source);
+            lines[0].0.splice(0..0, prefix);
+            items.push(ListItem::new(lines));
+        }
 
-        let stats_list = Paragraph::new(Text::from(vec![
-            Spans::from(format!("Total messages: {}", self.stats.total_messages)),
-            Spans::from(format!("Info messages: {}", self.stats.info_messages)),
-            Spans::from(format!("Warning messages: {}", self.stats.warning_messages)),
-            Spans::from(format!("Error messages: {}", self.stats.error_messages)),
-            Spans::from(format!("Trace messages: {}", self.stats.trace_messages)),
-        ]))
-        .block(Block::default().borders(Borders::ALL).title("Stats"))
-        .wrap(Wrap { trim: true });
-
-        if let Some(filter) = &self.filter {
-            let filtered_list = match filter {
-                LogFormatter::Info => info_list,
-                LogFormatter::Warning => warning_list,
-                LogFormatter::Error => error_list,
-                LogFormatter::Trace => trace_list,
-            };
-            f.render_widget(filtered_list, log_chunks[0]);
-        } else {
-            f.render_widget(info_list, log_chunks[0]);
-            f.render_widget(warning_list, log_chunks[1]);
-            f.render_widget(error_list, log_chunks[2]);
-            f.render_widget(trace_list, log_chunks[3]);
+        // Заголовок служит легендой: имена источников их цветами
+        let mut title = vec![Span::raw("Merged Logs:")];
+        for (index, path) in sources.iter().enumerate() {
+            title.push(Span::raw(" "));
+            title.push(Span::styled(
+                source_name(path),
+                Style::default().fg(SOURCE_COLORS[index % SOURCE_COLORS.len()]),
+            ));
         }
+
+        let list = List::new(items)
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title(Spans::from(title)),
+            )
+            .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+        let stats_list = self.stats_paragraph();
+
+        let column = self.columns.entry(None).or_default();
+        f.render_stateful_widget(list, area, &mut column.state);
         f.render_widget(stats_list, area);
     }
 
+    fn filter_bar(&self) -> Paragraph<'static> {
+        let editing = self.input.is_some();
+        let text = self
+            .input
+            .clone()
+            .unwrap_or_else(|| self.query_text.clone());
+        let shown = self
+            .logs
+            .entries
+            .iter()
+            .filter(|entry| self.visible(entry))
+            .count();
+
+        let mut lines = vec![Spans::from(vec![
+            Span::raw(text),
+            Span::styled(
+                if editing { "_" } else { "" },
+                Style::default().add_modifier(Modifier::SLOW_BLINK),
+            ),
+        ])];
+        if let (true, Some(err)) = (editing, &self.query_error) {
+            lines.push(Spans::from(Span::styled(
+                format!("{} {}", err.pointer(), err.message),
+                Style::default().fg(Color::Red),
+            )));
+        }
+
+        let title = if editing {
+            "Filter (Enter — apply, Esc — cancel)".to_string()
+        } else {
+            format!("Filter: {} of {}", shown, self.logs.entries.len())
+        };
+        Paragraph::new(Text::from(lines)).block(Block::default().borders(Borders::ALL).title(title))
+    }
+
+    fn stats_paragraph(&self) -> Paragraph<'static> {
+        let mut summary = self.stats.summary();
+        if self.paused {
+            summary.push(format!("PAUSED ({} new)", self.pending.len()));
+        }
+        Paragraph::new(Text::from(
+            summary.into_iter().map(Spans::from).collect::<Vec<_>>(),
+        ))
+        .block(Block::default().borders(Borders::ALL).title("Stats"))
+        .wrap(Wrap { trim: true })
+    }
+
     fn render_curve<B: tui::backend::Backend>(
         &self,
         f: &mut tui::Frame<B>,
@@ -253,7 +712,15 @@ impl App {
     }
 }
 
-pub fn run_app(logs: Logs, stats: LogStats, filter: Option<LogFormatter>) -> eyre::Result<()> {
+// Без `updates` показывается готовый снимок логов; с ним (режим --follow)
+// новые записи добавляются по мере поступления
+pub async fn run_app(
+    logs: Logs,
+    stats: LogStats,
+    filter: Option<LevelFilter>,
+    merge: bool,
+    mut updates: Option<UnboundedReceiver<LogEntry>>,
+) -> eyre::Result<()> {
     enable_raw_mode()?;
     let mut stdout = io::stdout();
     execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
@@ -261,18 +728,39 @@ pub fn run_app(logs: Logs, stats: LogStats, filter: Option<LogFormatter>) -> eyr
     let mut terminal = Terminal::new(backend)?;
 
     let mut app = App::new(logs, stats, filter);
+    app.set_merge(merge);
+    let mut events = EventStream::new();
 
-    loop {
+    let result = loop {
         let _ = app.render(&mut terminal);
 
-        if let Event::Key(key) = event::read()? {
-            match key.code {
-                KeyCode::Char('q') => break,
-                KeyCode::Char('t') => app.tab = (app.tab + 1) % 2,
-                _ => {}
-            }
+        tokio::select! {
+            event = events.next() => match event {
+                Some(Ok(Event::Key(key))) => {
+                    if !app.handle_key(key) {
+                        break Ok(());
+                    }
+                }
+                Some(Ok(_)) => {}
+                Some(Err(err)) => break Err(err.into()),
+                None => break Ok(()),
+            },
+            entry = next_update(&mut updates), if updates.is_some() => match entry {
+                Some(entry) => {
+                    app.push(entry);
+                    // Всё, что уже пришло, добавляется до следующей перерисовки
+                    if let Some(updates) = updates.as_mut() {
+                        while let Ok(entry) = updates.try_recv() {
+                            app.push(entry);
+                        }
+                    }
+                    app.update_curve();
+                }
+                // Источник закрыт: дальше работаем как со снимком
+                None => updates = None,
+            },
         }
-    }
+    };
 
     disable_raw_mode()?;
     execute!(
@@ -281,5 +769,44 @@ pub fn run_app(logs: Logs, stats: LogStats, filter: Option<LogFormatter>) -> eyr
         DisableMouseCapture
     )?;
     terminal.show_cursor()?;
-    Ok(())
+    result
+}
+
+// Многострочная запись (--multiline) показывается первой строкой с числом
+// скрытых строк, а развёрнутая по Enter — целиком
+fn message_lines(message: &str, expanded: bool, style: Style) -> Vec<Spans<'static>> {
+    let mut lines = message.lines();
+    let first = lines.next().unwrap_or_default().to_string();
+    let rest: Vec<String> = lines.map(String::from).collect();
+
+    if rest.is_empty() {
+        return vec![Spans::from(Span::styled(first, style))];
+    }
+    if !expanded {
+        return vec![Spans::from(vec![
+            Span::styled(first, style),
+            Span::styled(
+                format!(" [+{} lines]", rest.len()),
+                Style::default().add_modifier(Modifier::DIM),
+            ),
+        ])];
+    }
+    std::iter::once(first)
+        .chain(rest)
+        .map(|line| Spans::from(Span::styled(line, style)))
+        .collect()
+}
+
+fn source_name(path: &std::path::Path) -> String {
+    path.file_name()
+        .unwrap_or(path.as_os_str())
+        .to_string_lossy()
+        .into_owned()
+}
+
+async fn next_update(updates: &mut Option<UnboundedReceiver<LogEntry>>) -> Option<LogEntry> {
+    match updates {
+        Some(updates) => updates.recv().await,
+        None => None,
+    }
 }
@@ -0,0 +1,175 @@
+use crate::reader::{looks_binary, rotation_order};
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use std::collections::HashSet;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+// Файл или директория, которые пропущены при обходе
+#[derive(Debug)]
+pub struct Warning {
+    pub path: PathBuf,
+    pub reason: String,
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.path.display(), self.reason)
+    }
+}
+
+// Обход директорий с логами: фильтры по маскам, пропуск двоичных файлов
+// и защита от циклов из символических ссылок
+pub struct Walker {
+    recursive: bool,
+    include: Option<GlobSet>,
+    exclude: Option<GlobSet>,
+    // Канонические пути уже пройденных директорий, общие для всех корней:
+    // директория, указанная дважды (`-p /var/log -p /var/log/apt`), читается один раз
+    visited: HashSet<PathBuf>,
+    // Канонические пути директорий от корня до текущей: цикл — это ссылка на одну из них
+    ancestors: Vec<PathBuf>,
+    pub warnings: Vec<Warning>,
+}
+
+fn glob_set(patterns: &[String]) -> Result<Option<GlobSet>, globset::Error> {
+    if patterns.is_empty() {
+        return Ok(None);
+    }
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        builder.add(Glob::new(pattern)?);
+    }
+    builder.build().map(Some)
+}
+
+impl Walker {
+    pub fn new(
+        recursive: bool,
+        include: &[String],
+        exclude: &[String],
+    ) -> Result<Walker, globset::Error> {
+        Ok(Walker {
+            recursive,
+            include: glob_set(include)?,
+            exclude: glob_set(exclude)?,
+            visited: HashSet::new(),
+            ancestors: Vec::new(),
+            warnings: Vec::new(),
+        })
+    }
+
+    pub fn recursive(&self) -> bool {
+        self.recursive
+    }
+
+    // Новый обход тех же корней (--follow перечитывает директории): пройденные
+    // директории и предупреждения прошлого обхода забываются
+    pub fn restart(&mut self) {
+        self.visited.clear();
+        self.warnings.clear();
+    }
+
+    // Маска проверяется и по имени файла, и по пути относительно корня обхода
+    fn matches(set: &GlobSet, path: &Path, root: &Path) -> bool {
+        path.file_name().is_some_and(|name| set.is_match(name))
+            || path.strip_prefix(root).is_ok_and(|rel| set.is_match(rel))
+    }
+
+    fn warn(&mut self, path: &Path, reason: impl ToString) {
+        self.warnings.push(Warning {
+            path: path.to_path_buf(),
+            reason: reason.to_string(),
+        });
+    }
+
+    // Файлы логов в директории: в каждой директории ротации упорядочены
+    // от старых к новым, поддиректории обходятся после файлов по имени
+    pub fn files(&mut self, root: &Path) -> Vec<PathBuf> {
+        let mut files = Vec::new();
+        self.walk(root, root, &mut files);
+        files
+    }
+
+    fn walk(&mut self, dir: &Path, root: &Path, out: &mut Vec<PathBuf>) {
+        let canonical = match dir.canonicalize() {
+            Ok(canonical) => canonical,
+            Err(err) => {
+                self.warn(dir, err);
+                return;
+            }
+        };
+        if self.ancestors.contains(&canonical) {
+            self.warn(dir, "symlink loop skipped");
+            return;
+        }
+        if !self.visited.insert(canonical.clone()) {
+            self.warn(dir, "directory already read from another path");
+            return;
+        }
+
+        self.ancestors.push(canonical);
+        self.read_dir(dir, root, out);
+        self.ancestors.pop();
+    }
+
+    fn read_dir(&mut self, dir: &Path, root: &Path, out: &mut Vec<PathBuf>) {
+        let entries = match dir.read_dir() {
+            Ok(entries) => entries,
+            Err(err) => {
+                self.warn(dir, err);
+                return;
+            }
+        };
+
+        let mut files = Vec::new();
+        let mut dirs = Vec::new();
+        for entry in entries {
+            let path = match entry {
+                Ok(entry) => entry.path(),
+                Err(err) => {
+                    self.warn(dir, err);
+                    continue;
+                }
+            };
+
+            if let Some(exclude) = &self.exclude {
+                if Walker::matches(exclude, &path, root) {
+                    continue;
+                }
+            }
+
+            // metadata идёт по символическим ссылкам; битая ссылка — предупреждение
+            let meta = match std::fs::metadata(&path) {
+                Ok(meta) => meta,
+                Err(err) => {
+                    self.warn(&path, err);
+                    continue;
+                }
+            };
+
+            if meta.is_dir() {
+                if self.recursive {
+                    dirs.push(path);
+                }
+            } else if meta.is_file() {
+                if let Some(include) = &self.include {
+                    if !Walker::matches(include, &path, root) {
+                        continue;
+                    }
+                }
+                match looks_binary(&path) {
+                    Ok(false) => files.push(path),
+                    Ok(true) => self.warn(&path, "binary file skipped"),
+                    Err(err) => self.warn(&path, err),
+                }
+            }
+        }
+
+        out.extend(rotation_order(files));
+
+        dirs.sort();
+        for sub in dirs {
+            self.walk(&sub, root, out);
+        }
+    }
+}